@@ -0,0 +1,399 @@
+//! `#[derive(ToolArgs)]` for tool argument structs.
+//!
+//! Generates the JSON Schema sent in the request `tools` array from the
+//! struct's fields: Rust types map to JSON types, `Option<T>` fields are
+//! optional, doc comments become descriptions, `#[tool(enum = [...])]`
+//! restricts a field to a fixed set of values, and `#[tool(minimum = ..)]` /
+//! `#[tool(maximum = ..)]` bound numeric fields.
+//!
+//! The schema follows the names serde deserializes: `#[serde(rename)]` and
+//! `#[serde(rename_all)]` are applied, `#[serde(default)]` fields are
+//! optional, and `#[serde(skip)]` fields are left out. `#[serde(flatten)]`
+//! is rejected.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Expr, ExprArray, Fields, GenericArgument,
+    Lit, Meta, PathArguments, Token, Type,
+};
+
+#[proc_macro_derive(ToolArgs, attributes(tool))]
+pub fn derive_tool_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ToolArgs can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ToolArgs can only be derived for structs",
+            ))
+        }
+    };
+
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let mut inserts = Vec::new();
+    let mut required = Vec::new();
+    for field in fields {
+        let serde = SerdeAttrs::parse(&field.attrs)?;
+        if serde.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        let name = match (&serde.rename, &container.rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some((rule, span))) => rename_field(rule, &ident.unraw().to_string())
+                .ok_or_else(|| syn::Error::new(*span, format!("unknown rename_all rule `{}`", rule)))?,
+            (None, None) => ident.unraw().to_string(),
+        };
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+
+        let (ty, optional) = match option_inner(&field.ty) {
+            Some(inner) => (inner, true),
+            None => (&field.ty, false),
+        };
+        if !optional && !serde.default && !container.default {
+            required.push(name.clone());
+        }
+
+        let schema = type_schema(ty);
+        let description = doc_comment(&field.attrs).map(|doc| {
+            quote! { schema.insert("description".to_string(), ::groq_agent::__private::serde_json::Value::from(#doc)); }
+        });
+        let enum_values = attrs.enum_values.map(|values| {
+            quote! { schema.insert("enum".to_string(), ::groq_agent::__private::serde_json::json!([#(#values),*])); }
+        });
+        let minimum = attrs.minimum.map(|minimum| {
+            quote! { schema.insert("minimum".to_string(), ::groq_agent::__private::serde_json::json!(#minimum)); }
+        });
+        let maximum = attrs.maximum.map(|maximum| {
+            quote! { schema.insert("maximum".to_string(), ::groq_agent::__private::serde_json::json!(#maximum)); }
+        });
+
+        inserts.push(quote! {
+            {
+                let mut schema = match #schema {
+                    ::groq_agent::__private::serde_json::Value::Object(map) => map,
+                    _ => unreachable!("type schemas are always objects"),
+                };
+                #description
+                #enum_values
+                #minimum
+                #maximum
+                properties.insert(#name.to_string(), ::groq_agent::__private::serde_json::Value::Object(schema));
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::groq_agent::ToolArgs for #ident #ty_generics #where_clause {
+            fn parameters_schema() -> ::groq_agent::ToolFunctionParameters {
+                let mut properties = ::groq_agent::__private::serde_json::Map::new();
+                #(#inserts)*
+                ::groq_agent::ToolFunctionParameters::object(
+                    ::groq_agent::__private::serde_json::Value::Object(properties),
+                    vec![#(#required.to_string()),*],
+                )
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    enum_values: Option<Vec<Expr>>,
+    minimum: Option<Expr>,
+    maximum: Option<Expr>,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = FieldAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("tool")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("enum") {
+                    let values: ExprArray = meta.value()?.parse()?;
+                    parsed.enum_values = Some(values.elems.into_iter().collect());
+                    Ok(())
+                } else if meta.path.is_ident("minimum") {
+                    parsed.minimum = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("maximum") {
+                    parsed.maximum = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unsupported tool attribute, expected `enum`, `minimum` or `maximum`",
+                    ))
+                }
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+// The serde attributes that change which names and fields are deserialized
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<(String, proc_macro2::Span)>,
+    default: bool,
+    skip: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut parsed = SerdeAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+            for meta in metas {
+                let path = meta.path();
+                if path.is_ident("rename") {
+                    parsed.rename = deserialize_name(&meta)?;
+                } else if path.is_ident("rename_all") {
+                    parsed.rename_all = deserialize_name(&meta)?.map(|rule| (rule, meta.span()));
+                } else if path.is_ident("default") {
+                    parsed.default = true;
+                } else if path.is_ident("skip") || path.is_ident("skip_deserializing") {
+                    parsed.skip = true;
+                } else if path.is_ident("flatten") {
+                    return Err(syn::Error::new_spanned(
+                        &meta,
+                        "ToolArgs doesn't support `#[serde(flatten)]`; use a nested struct field",
+                    ));
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+// `rename = "x"`, or the `deserialize` half of `rename(serialize = .., deserialize = ..)`
+fn deserialize_name(meta: &Meta) -> syn::Result<Option<String>> {
+    match meta {
+        Meta::NameValue(nv) => string_literal(&nv.value).map(Some),
+        Meta::List(list) => {
+            let halves =
+                list.parse_args_with(Punctuated::<syn::MetaNameValue, Token![,]>::parse_terminated)?;
+            halves
+                .iter()
+                .find(|half| half.path.is_ident("deserialize"))
+                .map(|half| string_literal(&half.value))
+                .transpose()
+        }
+        Meta::Path(path) => Err(syn::Error::new_spanned(path, "expected a name")),
+    }
+}
+
+fn string_literal(expr: &Expr) -> syn::Result<String> {
+    match expr {
+        Expr::Lit(syn::ExprLit { lit: Lit::Str(s), .. }) => Ok(s.value()),
+        _ => Err(syn::Error::new_spanned(expr, "expected a string literal")),
+    }
+}
+
+// Serde's `rename_all` rules, applied to a snake_case field name
+fn rename_field(rule: &str, field: &str) -> Option<String> {
+    let pascal = || {
+        field
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default()
+            })
+            .collect::<String>()
+    };
+    let renamed = match rule {
+        "lowercase" | "snake_case" => field.to_string(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => field.to_ascii_uppercase(),
+        "PascalCase" => pascal(),
+        "camelCase" => {
+            let pascal = pascal();
+            let mut chars = pascal.chars();
+            chars
+                .next()
+                .map(|first| first.to_lowercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.to_ascii_uppercase().replace('_', "-"),
+        _ => return None,
+    };
+    Some(renamed)
+}
+
+/// Expression evaluating to the JSON Schema object for `ty`.
+fn type_schema(ty: &Type) -> TokenStream2 {
+    let json = quote! { ::groq_agent::__private::serde_json::json! };
+    if let Some(item) = generic_inner(ty, "Vec") {
+        let items = type_schema(item);
+        return quote! { #json({ "type": "array", "items": #items }) };
+    }
+
+    let json_type = match last_segment(ty).as_deref() {
+        Some("f32" | "f64") => "number",
+        Some(
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
+            | "u128" | "usize",
+        ) => "integer",
+        Some("bool") => "boolean",
+        Some("String" | "str" | "char") => "string",
+        // Anything else is expected to be a nested `ToolArgs` struct.
+        _ => {
+            return quote! {
+                ::groq_agent::__private::serde_json::to_value(
+                    <#ty as ::groq_agent::ToolArgs>::parameters_schema()
+                )
+                .expect("tool parameter schema serializes")
+            }
+        }
+    };
+    quote! { #json({ "type": #json_type }) }
+}
+
+fn last_segment(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        Type::Reference(reference) => last_segment(&reference.elem),
+        _ => None,
+    }
+}
+
+fn option_inner(ty: &Type) -> Option<&Type> {
+    generic_inner(ty, "Option")
+}
+
+fn generic_inner<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(nv) => match &nv.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(s) => Some(s.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn error(input: DeriveInput) -> String {
+        expand(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_non_structs() {
+        assert_eq!(
+            error(parse_quote! { enum Mode { Fast, Slow } }),
+            "ToolArgs can only be derived for structs"
+        );
+        assert_eq!(
+            error(parse_quote! { struct Pair(u32, u32); }),
+            "ToolArgs can only be derived for structs with named fields"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_attributes() {
+        assert!(error(parse_quote! { struct Args { #[tool(pattern = "x")] a: String } })
+            .starts_with("unsupported tool attribute"));
+        assert!(error(parse_quote! { struct Args { #[serde(flatten)] inner: Inner } })
+            .contains("flatten"));
+        assert_eq!(
+            error(parse_quote! { #[serde(rename_all = "Title Case")] struct Args { a: String } }),
+            "unknown rename_all rule `Title Case`"
+        );
+    }
+
+    #[test]
+    fn expands_with_deserialized_names() {
+        let expanded = expand(&parse_quote! {
+            #[serde(rename_all = "kebab-case")]
+            struct Args {
+                #[serde(rename(serialize = "out", deserialize = "in"))]
+                renamed: String,
+                max_results: u32,
+                #[serde(skip_deserializing)]
+                skipped: String,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(expanded.contains("\"in\""));
+        assert!(expanded.contains("\"max-results\""));
+        assert!(!expanded.contains("\"out\"") && !expanded.contains("skipped"));
+    }
+
+    #[test]
+    fn renames_fields_like_serde() {
+        let cases = [
+            ("lowercase", "page_size"),
+            ("UPPERCASE", "PAGE_SIZE"),
+            ("PascalCase", "PageSize"),
+            ("camelCase", "pageSize"),
+            ("snake_case", "page_size"),
+            ("SCREAMING_SNAKE_CASE", "PAGE_SIZE"),
+            ("kebab-case", "page-size"),
+            ("SCREAMING-KEBAB-CASE", "PAGE-SIZE"),
+        ];
+        for (rule, expected) in cases {
+            assert_eq!(rename_field(rule, "page_size").as_deref(), Some(expected), "{}", rule);
+        }
+        assert_eq!(rename_field("Train-Case", "page_size"), None);
+    }
+}
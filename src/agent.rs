@@ -0,0 +1,626 @@
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+use tracing::{field, Instrument, Span};
+
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::context::{self, ContextStrategy, TrimPolicy};
+use crate::events::{AgentEvent, EventBus, EventHandler};
+use crate::providers::{self, LlmProvider};
+use crate::tools::{validate_arguments, Tool, ToolContext, ToolError, ToolRegistry};
+use crate::usage::UsageTotals;
+use crate::{default_tool_type, ChatRequest, ChatResponse, Conversation, FunctionCall, Message, ToolCall};
+
+lazy_static! {
+    static ref FUNCTION_REGEX: Regex = Regex::new(r"<function=(\w+)(\{.*?\})>").unwrap();
+}
+
+/// Bounds on a single user turn, so a model that keeps calling tools can't
+/// loop forever and burn quota.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AgentLimits {
+    /// Tool round-trips allowed before the model must answer. With 0 the
+    /// model isn't offered any tools.
+    pub max_tool_rounds: u32,
+    /// Wall-clock budget for the whole turn, including tool execution.
+    /// Tool calls still running when it expires are reported to the model
+    /// as timed out, and it must answer with what it has; a model request
+    /// still running fails the turn.
+    pub turn_timeout_secs: u64,
+    /// Stop as soon as the model repeats a call it already made this turn,
+    /// with the same arguments. The repeat isn't run; the model gets a
+    /// `repeated_call` error in its place.
+    pub detect_repeated_calls: bool,
+}
+
+impl Default for AgentLimits {
+    fn default() -> Self {
+        AgentLimits {
+            max_tool_rounds: 8,
+            turn_timeout_secs: 120,
+            detect_repeated_calls: true,
+        }
+    }
+}
+
+// Native `tool_calls` take precedence; inline `<function=...>` tags are only
+// scraped from the content for models that don't emit structured calls.
+fn extract_tool_calls(message: &Message) -> Vec<ToolCall> {
+    if let Some(tool_calls) = message.tool_calls.as_ref().filter(|calls| !calls.is_empty()) {
+        return tool_calls.clone();
+    }
+
+    FUNCTION_REGEX
+        .captures_iter(&message.content)
+        .enumerate()
+        .map(|(index, captures)| ToolCall {
+            id: format!("inline_call_{}", index),
+            call_type: default_tool_type(),
+            function: FunctionCall {
+                name: captures[1].to_string(),
+                arguments: captures[2].to_string(),
+            },
+        })
+        .collect()
+}
+
+#[tracing::instrument(
+    name = "llm_request",
+    skip_all,
+    fields(
+        provider = provider.name(),
+        model = %request_payload.model,
+        messages = request_payload.messages.len(),
+        stream,
+        latency_ms = field::Empty,
+        prompt_tokens = field::Empty,
+        completion_tokens = field::Empty,
+        status = field::Empty,
+    )
+)]
+pub(crate) async fn send_chat_request(
+    provider: &dyn LlmProvider,
+    request_payload: &ChatRequest,
+    stream: bool,
+    events: &EventBus,
+) -> Result<ChatResponse, ApiError> {
+    events.emit(AgentEvent::RequestSent {
+        model: request_payload.model.clone(),
+        messages: request_payload.messages.len(),
+    });
+    let started = Instant::now();
+    let result = if stream {
+        provider
+            .chat_stream(request_payload, &mut |token| {
+                events.emit(AgentEvent::TokenDelta(token.to_string()))
+            })
+            .await
+    } else {
+        provider.chat(request_payload).await
+    };
+
+    let span = Span::current();
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+    match &result {
+        Ok(response) => {
+            let usage = response.usage();
+            span.record("prompt_tokens", usage.prompt_tokens);
+            span.record("completion_tokens", usage.completion_tokens);
+            span.record("status", "ok");
+        }
+        Err(e) => {
+            span.record("status", e.kind());
+            tracing::error!(error = %e, "request failed");
+        }
+    }
+    result
+}
+
+async fn execute_tool_call(
+    tool: Option<std::sync::Arc<dyn Tool>>,
+    tool_call: &ToolCall,
+    events: &EventBus,
+) -> Result<String, ToolError> {
+    let function_name = tool_call.function.name.as_str();
+    let params_str = tool_call.function.arguments.as_str();
+
+    let tool = tool.ok_or_else(|| ToolError::NotFound(function_name.to_string()))?;
+    // Some models send an empty string for tools without parameters
+    let params = if params_str.trim().is_empty() {
+        serde_json::json!({})
+    } else {
+        serde_json::from_str(params_str)
+            .map_err(|e| ToolError::InvalidArguments(format!("arguments are not valid JSON: {}", e)))?
+    };
+    validate_arguments(&tool.parameters_schema(), &params).map_err(ToolError::Validation)?;
+
+    events.emit(AgentEvent::ToolStarted {
+        call_id: tool_call.id.clone(),
+        name: function_name.to_string(),
+    });
+    let ctx = ToolContext {
+        call_id: tool_call.id.clone(),
+    };
+    let output = tool.call(params, &ctx).await?;
+    Ok(output.content)
+}
+
+// Runs every call from one assistant turn concurrently; the resulting `tool`
+// messages come back in the order the model issued the calls. Calls marked
+// in `repeated` are answered with an error instead of running, and calls
+// still running after `timeout` are aborted and answered with an error.
+async fn execute_tool_calls(
+    registry: &ToolRegistry,
+    tool_calls: &[ToolCall],
+    repeated: &[bool],
+    events: &EventBus,
+    timeout: Duration,
+) -> Vec<Message> {
+    for tool_call in tool_calls {
+        events.emit(AgentEvent::ToolCallRequested(tool_call.clone()));
+    }
+    let handles: Vec<_> = tool_calls
+        .iter()
+        .cloned()
+        .zip(repeated)
+        .map(|(tool_call, &repeated)| {
+            if repeated {
+                return None;
+            }
+            let tool = registry.get(&tool_call.function.name);
+            let events = events.clone();
+            // Created here, so the spawned task's span is a child of the turn
+            let span = tracing::info_span!(
+                "tool_call",
+                tool = %tool_call.function.name,
+                call_id = %tool_call.id,
+                args_hash = %arguments_hash(&tool_call),
+                duration_ms = field::Empty,
+                outcome = field::Empty,
+            );
+            let task = async move {
+                let started = Instant::now();
+                let result = execute_tool_call(tool, &tool_call, &events).await;
+                let span = Span::current();
+                span.record("duration_ms", started.elapsed().as_millis() as u64);
+                span.record(
+                    "outcome",
+                    match &result {
+                        Ok(_) => "ok",
+                        Err(e) => e.kind(),
+                    },
+                );
+                // Reported from the task so results show up as they finish
+                events.emit(match &result {
+                    Ok(output) => AgentEvent::ToolFinished {
+                        call_id: tool_call.id.clone(),
+                        name: tool_call.function.name.clone(),
+                        output: output.clone(),
+                    },
+                    Err(e) => AgentEvent::ToolFailed {
+                        call_id: tool_call.id.clone(),
+                        name: tool_call.function.name.clone(),
+                        error: e.to_string(),
+                    },
+                });
+                result
+            };
+            Some(tokio::spawn(task.instrument(span)))
+        })
+        .collect();
+
+    let expired = tokio::time::sleep(timeout);
+    tokio::pin!(expired);
+    let mut results = Vec::with_capacity(handles.len());
+    for (tool_call, handle) in tool_calls.iter().zip(handles) {
+        let joined = match handle {
+            Some(mut handle) => tokio::select! {
+                // Calls that already finished keep their result
+                biased;
+                joined = &mut handle => joined.map_err(|e| {
+                    ToolError::Execution(format!("Function '{}' panicked: {}", tool_call.function.name, e))
+                }),
+                _ = &mut expired => {
+                    handle.abort();
+                    Err(ToolError::Execution("timed out".to_string()))
+                }
+            },
+            None => Err(ToolError::Repeated(tool_call.function.name.clone())),
+        };
+        let result = joined.unwrap_or_else(|error| {
+            events.emit(AgentEvent::ToolFailed {
+                call_id: tool_call.id.clone(),
+                name: tool_call.function.name.clone(),
+                error: error.to_string(),
+            });
+            Err(error)
+        });
+        let content = match result {
+            Ok(content) => content,
+            Err(e) => e.to_tool_content(),
+        };
+        results.push(Message::tool(tool_call, content));
+    }
+    results
+}
+
+// Identifies a call by name and arguments, ignoring key order and whitespace
+fn call_signature(tool_call: &ToolCall) -> (String, String) {
+    let arguments = serde_json::from_str::<serde_json::Value>(&tool_call.function.arguments)
+        .map(|arguments| arguments.to_string())
+        .unwrap_or_else(|_| tool_call.function.arguments.clone());
+    (tool_call.function.name.clone(), arguments)
+}
+
+// Stable fingerprint of a call's normalized arguments, so traces can show
+// repeated calls without logging argument values
+fn arguments_hash(tool_call: &ToolCall) -> String {
+    let mut hasher = DefaultHasher::new();
+    call_signature(tool_call).1.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Answers the last user message in `conversation`, running tool round-trips
+/// until the model replies without calling a tool. Once a limit from
+/// `config.limits` is hit, the model is told so and must answer with the
+/// results it already has. Progress is reported on `events`. Returns the
+/// token usage of every request made.
+///
+/// `trim_policy` chooses what to send when the history outgrows the context
+/// window; `None` uses the one `config.context.strategy` selects.
+#[tracing::instrument(
+    name = "turn",
+    skip_all,
+    err,
+    fields(
+        model = %config.model,
+        rounds = field::Empty,
+        requests = field::Empty,
+        prompt_tokens = field::Empty,
+        completion_tokens = field::Empty,
+        outcome = field::Empty,
+    )
+)]
+pub async fn run_turn(
+    provider: &dyn LlmProvider,
+    registry: &ToolRegistry,
+    conversation: &mut Conversation,
+    config: &Config,
+    trim_policy: Option<&dyn TrimPolicy>,
+    events: &EventBus,
+) -> Result<UsageTotals> {
+    let limits = &config.limits;
+    let started = Instant::now();
+    let budget = Duration::from_secs(limits.turn_timeout_secs);
+    // With no rounds allowed the model isn't offered any tools
+    let tools_allowed = limits.max_tool_rounds > 0;
+    let tools = if tools_allowed { registry.definitions() } else { Vec::new() };
+    let mut tool_choice = config.tool_choice_value();
+    let mut rounds = 0;
+    let mut seen_calls = HashSet::new();
+    let mut final_answer_forced = false;
+    let mut turn_usage = UsageTotals::default();
+    let price = config.price_for(&config.model);
+    let default_policy = config.context.policy();
+    let trim_policy = trim_policy.unwrap_or(default_policy.as_ref());
+    let message_budget =
+        context::message_budget(&config.context, &config.model, &tools, config.max_tokens);
+
+    loop {
+        if let (ContextStrategy::Summarize, Some(budget)) = (config.context.strategy, message_budget) {
+            match context::summarize_oldest(provider, config, conversation, budget, events).await {
+                Ok(Some(usage)) => {
+                    turn_usage.record(&usage, price);
+                    events.emit(AgentEvent::Usage {
+                        request: usage,
+                        turn: turn_usage.clone(),
+                    });
+                    if let Some(compaction) = conversation.compactions().last() {
+                        events.emit(AgentEvent::ContextCompacted(compaction.clone()));
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => events.emit(AgentEvent::SummarizationFailed { error: e.to_string() }),
+            }
+        }
+
+        // Trimming only shapes the request; the full history stays in the
+        // conversation
+        let messages = match message_budget {
+            Some(budget) => trim_policy.trim(conversation, budget),
+            None => conversation.messages().to_vec(),
+        };
+        if messages.len() < conversation.len() {
+            events.emit(AgentEvent::ContextTrimmed {
+                dropped_messages: conversation.len() - messages.len(),
+            });
+        }
+        let mut request_payload = ChatRequest::new(config, messages, tools.clone());
+        if !request_payload.tools.is_empty() {
+            request_payload.tool_choice = tool_choice.clone();
+        }
+
+        let request = send_chat_request(provider, &request_payload, config.stream, events);
+        let chat_response = if final_answer_forced {
+            // The forced answer may run past the budget that triggered it;
+            // the retry deadline still bounds it
+            request.await?
+        } else {
+            match tokio::time::timeout(budget.saturating_sub(started.elapsed()), request).await {
+                Ok(response) => response?,
+                Err(_) => bail!("the {}s time budget for this turn ran out", limits.turn_timeout_secs),
+            }
+        };
+        let request_usage = chat_response.usage();
+        turn_usage.record(&request_usage, price);
+        events.emit(AgentEvent::Usage {
+            request: request_usage,
+            turn: turn_usage.clone(),
+        });
+        let Some(choice) = chat_response.choices.into_iter().next() else {
+            bail!("the model returned no choices");
+        };
+        let mut message = match (choice.message, choice.text) {
+            (Some(message), _) => message,
+            (None, Some(text)) => Message::new("assistant", text),
+            (None, None) => bail!("the model returned an empty choice"),
+        };
+
+        let tool_calls = extract_tool_calls(&message);
+        if tool_calls.is_empty() || final_answer_forced || !tools_allowed {
+            events.emit(AgentEvent::FinalAnswer {
+                content: message.content.clone(),
+            });
+            message.tool_calls = None;
+            conversation.push(message);
+
+            let span = Span::current();
+            span.record("rounds", rounds);
+            span.record("requests", turn_usage.requests);
+            span.record("prompt_tokens", turn_usage.usage.prompt_tokens);
+            span.record("completion_tokens", turn_usage.usage.completion_tokens);
+            span.record("outcome", if final_answer_forced { "limit_reached" } else { "answered" });
+            return Ok(turn_usage);
+        }
+
+        // The history must carry the exact calls the `tool` messages
+        // answer, including ids synthesized for inline tags.
+        message.tool_calls = Some(tool_calls.clone());
+        conversation.push(message);
+
+        // Repeats are caught before they run, including within one round
+        let repeated: Vec<bool> = tool_calls
+            .iter()
+            .map(|tool_call| limits.detect_repeated_calls && !seen_calls.insert(call_signature(tool_call)))
+            .collect();
+        let remaining = budget.saturating_sub(started.elapsed());
+        for result in execute_tool_calls(registry, &tool_calls, &repeated, events, remaining).await {
+            conversation.push(result);
+        }
+        rounds += 1;
+
+        let repeated = repeated.contains(&true);
+        let limit_reached = if rounds >= limits.max_tool_rounds {
+            Some(format!("the maximum of {} tool rounds was used", limits.max_tool_rounds))
+        } else if started.elapsed() >= budget {
+            Some(format!("the {}s time budget for this turn ran out", limits.turn_timeout_secs))
+        } else if repeated {
+            Some("a tool was called again with the same arguments".to_string())
+        } else {
+            None
+        };
+
+        if let Some(reason) = limit_reached {
+            tracing::warn!(reason = %reason, "tool limit reached");
+            events.emit(AgentEvent::ToolLimitReached {
+                reason: reason.clone(),
+            });
+            conversation.push(Message::new(
+                "system",
+                format!(
+                    "Tool limit reached: {}. Do not call any more tools. Answer the user now using the results you already have.",
+                    reason
+                ),
+            ));
+            tool_choice = serde_json::Value::from("none");
+            final_answer_forced = true;
+        } else if config.tool_choice != "none" {
+            // Forcing another call once the results are in would never end
+            tool_choice = serde_json::Value::from("auto");
+        }
+    }
+}
+
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a helpful assistant with access to tools. Call them whenever they help answer the user's request, then use their results to give a friendly response.";
+
+/// A model, its tools and its settings, ready to answer. Build one with
+/// [`Agent::builder`].
+///
+/// `run` keeps a conversation inside the agent, which suits a single chat.
+/// `chat` works on a conversation owned by the caller, so one agent can
+/// serve many chats.
+pub struct Agent {
+    provider: Box<dyn LlmProvider>,
+    tools: ToolRegistry,
+    config: Config,
+    system_prompt: String,
+    trim_policy: Option<Box<dyn TrimPolicy>>,
+    events: EventBus,
+    conversation: Conversation,
+    usage: UsageTotals,
+}
+
+impl Agent {
+    pub fn builder() -> AgentBuilder {
+        AgentBuilder::default()
+    }
+
+    /// Sends `input` as the next user message of the agent's own
+    /// conversation and returns the final answer. A failed turn is removed
+    /// from the history so the conversation can carry on.
+    pub async fn run(&mut self, input: impl Into<String>) -> Result<String> {
+        let turn_start = self.conversation.len();
+        self.conversation.push(Message::new("user", input));
+        match run_turn(
+            self.provider.as_ref(),
+            &self.tools,
+            &mut self.conversation,
+            &self.config,
+            self.trim_policy.as_deref(),
+            &self.events,
+        )
+        .await {
+            Ok(turn_usage) => {
+                self.usage.merge(&turn_usage);
+                Ok(self
+                    .conversation
+                    .messages()
+                    .last()
+                    .map(|message| message.content.clone())
+                    .unwrap_or_default())
+            }
+            Err(e) => {
+                self.conversation.truncate(turn_start);
+                Err(e)
+            }
+        }
+    }
+
+    /// Answers the latest user message in `conversation`, appending the
+    /// model's tool calls, their results and the final answer to it.
+    pub async fn chat(&self, conversation: &mut Conversation) -> Result<UsageTotals> {
+        run_turn(
+            self.provider.as_ref(),
+            &self.tools,
+            conversation,
+            &self.config,
+            self.trim_policy.as_deref(),
+            &self.events,
+        )
+        .await
+    }
+
+    /// A new conversation that starts with the agent's system prompt.
+    pub fn new_conversation(&self) -> Conversation {
+        Conversation::new(&self.system_prompt)
+    }
+
+    pub fn conversation(&self) -> &Conversation {
+        &self.conversation
+    }
+
+    /// Usage of every `run` call so far.
+    pub fn usage(&self) -> &UsageTotals {
+        &self.usage
+    }
+
+    pub fn provider(&self) -> &dyn LlmProvider {
+        self.provider.as_ref()
+    }
+
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Replaces the request settings. The provider is chosen when the agent
+    /// is built, so changes to `provider`, `base_url`, `retry` or
+    /// `rate_limit` need a new agent.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+}
+
+#[derive(Default)]
+pub struct AgentBuilder {
+    provider: Option<Box<dyn LlmProvider>>,
+    tools: ToolRegistry,
+    config: Config,
+    system_prompt: Option<String>,
+    trim_policy: Option<Box<dyn TrimPolicy>>,
+    events: EventBus,
+}
+
+impl AgentBuilder {
+    /// The provider to talk to. Without one, `build` creates it from the
+    /// config and the provider's API key environment variable.
+    pub fn provider(mut self, provider: impl LlmProvider + 'static) -> Self {
+        self.provider = Some(Box::new(provider));
+        self
+    }
+
+    pub fn tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools = self.tools.register(tool);
+        self
+    }
+
+    pub fn system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(system_prompt.into());
+        self
+    }
+
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.config.model = model.into();
+        self
+    }
+
+    pub fn limits(mut self, limits: AgentLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Replaces the trim policy that `context.strategy` would pick, for
+    /// histories that need their own rules about what to drop.
+    pub fn trim_policy(mut self, trim_policy: Box<dyn TrimPolicy>) -> Self {
+        self.trim_policy = Some(trim_policy);
+        self
+    }
+
+    /// Adds a subscriber for the agent's events: a closure, a channel
+    /// sender, or `ConsolePrinter` for the REPL's status lines.
+    pub fn subscribe(mut self, handler: impl EventHandler + 'static) -> Self {
+        self.events.subscribe(handler);
+        self
+    }
+
+    pub fn build(self) -> Result<Agent> {
+        let mut provider = match self.provider {
+            Some(provider) => provider,
+            None => providers::from_config(&self.config)?,
+        };
+        provider.set_events(self.events.clone());
+        let system_prompt = self
+            .system_prompt
+            .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string());
+        Ok(Agent {
+            provider,
+            tools: self.tools,
+            conversation: Conversation::new(&system_prompt),
+            config: self.config,
+            system_prompt,
+            trim_policy: self.trim_policy,
+            events: self.events,
+            usage: UsageTotals::default(),
+        })
+    }
+}
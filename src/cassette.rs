@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::error::ApiError;
+use crate::transport::{HttpRequest, Transport};
+
+// Credentials are never written to cassettes and never affect matching
+const IGNORED_HEADERS: &[&str] = &["authorization", "x-api-key"];
+
+/// Record or replay provider traffic instead of only talking to the network.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CassetteConfig {
+    /// Save every request and response to this file.
+    pub record: Option<PathBuf>,
+    /// Serve responses from this file; no network access or API key needed.
+    pub replay: Option<PathBuf>,
+}
+
+/// Request/response pairs saved by a `RecordingTransport`, in the order
+/// they happened.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interaction {
+    pub request: HttpRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Raw body, so streamed responses keep their server-sent events.
+    pub body: String,
+}
+
+impl Cassette {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read cassette {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("invalid cassette {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("failed to write cassette {}", path.display()))
+    }
+}
+
+impl RecordedResponse {
+    fn to_response(&self) -> Result<Response, ApiError> {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder
+            .body(self.body.clone())
+            .map_err(|e| ApiError::Decode(format!("invalid recorded response: {}", e)))?;
+        Ok(Response::from(response))
+    }
+}
+
+fn redact(request: &HttpRequest) -> HttpRequest {
+    let mut request = request.clone();
+    request
+        .headers
+        .retain(|name, _| !IGNORED_HEADERS.contains(&name.as_str()));
+    request
+}
+
+/// Sends requests through `inner` and saves every exchange to a cassette
+/// file, rewriting it after each response so nothing is lost on exit.
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingTransport {
+    /// Starts a new cassette at `path`, replacing any existing file.
+    pub fn new(inner: Arc<dyn Transport>, path: impl Into<PathBuf>) -> Self {
+        RecordingTransport {
+            inner,
+            path: path.into(),
+            cassette: Mutex::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<Response, ApiError> {
+        let response = self.inner.send(request).await?;
+        let recorded = RecordedResponse {
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                .collect(),
+            body: response.text().await?,
+        };
+        let replay = recorded.to_response();
+
+        let mut cassette = self.cassette.lock().unwrap_or_else(|e| e.into_inner());
+        cassette.interactions.push(Interaction {
+            request: redact(request),
+            response: recorded,
+        });
+        if let Err(e) = cassette.save(&self.path) {
+            tracing::warn!(error = %e, "failed to save cassette");
+        }
+        replay
+    }
+}
+
+/// Serves responses from a cassette instead of the network. A request gets
+/// the first unused interaction with the same method, URL, body and headers,
+/// auth headers aside; identical requests, such as retries, get successive
+/// responses.
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<Option<Interaction>>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        ReplayTransport {
+            interactions: Mutex::new(cassette.interactions.into_iter().map(Some).collect()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Interactions not served yet. Tests can assert this reaches zero.
+    pub fn remaining(&self) -> usize {
+        let interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        interactions.iter().flatten().count()
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<Response, ApiError> {
+        let request = redact(request);
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        let interaction = interactions
+            .iter_mut()
+            .find(|slot| slot.as_ref().is_some_and(|interaction| interaction.request == request))
+            .and_then(Option::take)
+            .ok_or_else(|| ApiError::InvalidRequest {
+                status: StatusCode::NOT_IMPLEMENTED,
+                message: format!(
+                    "no recorded interaction left for {} {}",
+                    request.method, request.url
+                ),
+            })?;
+        interaction.response.to_response()
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{bail, Result};
+use std::sync::Arc;
+
+use crate::session::{Session, SessionStore};
+use crate::tools::ToolRegistry;
+
+pub mod builtin;
+
+/// A `/name args` command typed at the REPL prompt. Implementations are
+/// registered in a `CommandRegistry`, so tool crates can ship their own
+/// commands alongside their tools.
+pub trait Command: Send + Sync {
+    fn name(&self) -> &str;
+    /// Argument synopsis shown by `/help`, e.g. `<name>`.
+    fn args(&self) -> &str {
+        ""
+    }
+    fn description(&self) -> &str;
+    fn run(&self, args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome>;
+}
+
+/// What the REPL can see and change while running a command.
+pub struct CommandContext<'a> {
+    pub session: &'a mut Session,
+    pub store: &'a SessionStore,
+    pub tools: &'a ToolRegistry,
+    pub commands: &'a CommandRegistry,
+}
+
+/// What the REPL does once a command has run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandOutcome {
+    /// Prompt for the next input.
+    Continue,
+    /// Send this message as a new user turn.
+    Send(String),
+    Exit,
+}
+
+#[derive(Default, Clone)]
+pub struct CommandRegistry {
+    commands: Vec<Arc<dyn Command>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding every built-in command.
+    pub fn builtin() -> Self {
+        Self::new()
+            .register(builtin::Help)
+            .register(builtin::Model)
+            .register(builtin::System)
+            .register(builtin::Tools)
+            .register(builtin::Clear)
+            .register(builtin::History)
+            .register(builtin::Save)
+            .register(builtin::Load)
+            .register(builtin::Usage)
+            .register(builtin::Retry)
+            .register(builtin::Undo)
+            .register(builtin::Exit)
+    }
+
+    /// Adds a command, replacing any previously registered command with the same name.
+    pub fn register(mut self, command: impl Command + 'static) -> Self {
+        self.commands.retain(|existing| existing.name() != command.name());
+        self.commands.push(Arc::new(command));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Command>> {
+        self.commands
+            .iter()
+            .find(|command| command.name() == name)
+            .cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Command>> {
+        self.commands.iter()
+    }
+
+    /// Runs `input` if it is a slash command. Returns `None` for ordinary
+    /// messages, which go to the model.
+    pub fn dispatch(&self, input: &str, ctx: &mut CommandContext<'_>) -> Option<Result<CommandOutcome>> {
+        let line = input.strip_prefix('/')?;
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        Some(match self.get(name) {
+            Some(command) => command.run(args.trim(), ctx),
+            None => Err(anyhow::anyhow!("unknown command /{}. Type /help for the list", name)),
+        })
+    }
+}
+
+// Shared by commands that need an argument
+fn require<'a>(args: &'a str, synopsis: &str) -> Result<&'a str> {
+    if args.is_empty() {
+        bail!("usage: {}", synopsis);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Conversation, Message};
+
+    struct Echo;
+
+    impl Command for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Send the arguments as a message"
+        }
+
+        fn run(&self, args: &str, _ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+            Ok(CommandOutcome::Send(args.to_string()))
+        }
+    }
+
+    // system, a completed turn, then a second completed turn
+    fn session() -> Session {
+        let mut conversation = Conversation::new("You are terse.");
+        conversation.push(Message::new("user", "hi"));
+        conversation.push(Message::new("assistant", "Hello."));
+        conversation.push(Message::new("user", "what is 1 + 2?"));
+        conversation.push(Message::new("assistant", "3"));
+        Session::new(&Config::default(), conversation)
+    }
+
+    fn dispatch(commands: &CommandRegistry, input: &str, session: &mut Session) -> Option<Result<CommandOutcome>> {
+        let store = SessionStore::new(std::env::temp_dir().join("groq-agent-unused-sessions"));
+        let tools = ToolRegistry::new();
+        let mut ctx = CommandContext {
+            session,
+            store: &store,
+            tools: &tools,
+            commands,
+        };
+        commands.dispatch(input, &mut ctx)
+    }
+
+    fn contents(session: &Session) -> Vec<&str> {
+        session
+            .conversation
+            .messages()
+            .iter()
+            .map(|message| message.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn dispatch_only_handles_slash_commands() {
+        let commands = CommandRegistry::builtin().register(Echo);
+        let mut session = session();
+
+        assert!(dispatch(&commands, "hello /echo", &mut session).is_none());
+        let outcome = dispatch(&commands, "/echo   two words ", &mut session).unwrap().unwrap();
+        assert_eq!(outcome, CommandOutcome::Send("two words".to_string()));
+        let error = dispatch(&commands, "/nope", &mut session).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "unknown command /nope. Type /help for the list");
+    }
+
+    #[test]
+    fn registering_a_name_again_replaces_the_command() {
+        let commands = CommandRegistry::builtin().register(Echo);
+        let count = commands.iter().count();
+        let commands = commands.register(Echo);
+
+        assert_eq!(commands.iter().count(), count);
+        assert_eq!(commands.iter().last().unwrap().name(), "echo");
+    }
+
+    #[test]
+    fn retry_resends_a_failed_input_and_keeps_the_history() {
+        let commands = CommandRegistry::builtin();
+        let mut session = session();
+        session.failed_input = Some("what is 2 + 3?".to_string());
+
+        let outcome = dispatch(&commands, "/retry", &mut session).unwrap().unwrap();
+
+        assert_eq!(outcome, CommandOutcome::Send("what is 2 + 3?".to_string()));
+        assert!(session.failed_input.is_none());
+        assert_eq!(session.conversation.len(), 5);
+    }
+
+    #[test]
+    fn retry_replaces_the_last_completed_turn() {
+        let commands = CommandRegistry::builtin();
+        let mut session = session();
+
+        let outcome = dispatch(&commands, "/retry", &mut session).unwrap().unwrap();
+
+        assert_eq!(outcome, CommandOutcome::Send("what is 1 + 2?".to_string()));
+        assert_eq!(contents(&session), ["You are terse.", "hi", "Hello."]);
+    }
+
+    #[test]
+    fn retry_needs_a_turn() {
+        let commands = CommandRegistry::builtin();
+        let mut session = Session::new(&Config::default(), Conversation::new("You are terse."));
+
+        let error = dispatch(&commands, "/retry", &mut session).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "nothing to retry yet");
+    }
+
+    #[test]
+    fn undo_removes_the_last_turn_until_none_are_left() {
+        let commands = CommandRegistry::builtin();
+        let mut session = session();
+
+        assert_eq!(dispatch(&commands, "/undo", &mut session).unwrap().unwrap(), CommandOutcome::Continue);
+        assert_eq!(contents(&session), ["You are terse.", "hi", "Hello."]);
+        dispatch(&commands, "/undo", &mut session).unwrap().unwrap();
+        assert_eq!(contents(&session), ["You are terse."]);
+        let error = dispatch(&commands, "/undo", &mut session).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "nothing to undo");
+    }
+}
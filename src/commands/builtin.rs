@@ -0,0 +1,313 @@
+use anyhow::{bail, Result};
+
+use super::{require, Command, CommandContext, CommandOutcome};
+use crate::context::estimate_tokens;
+use crate::session::Session;
+use crate::Conversation;
+
+pub struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &str {
+        "help"
+    }
+
+    fn description(&self) -> &str {
+        "List the available commands"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        for command in ctx.commands.iter() {
+            let synopsis = format!("/{} {}", command.name(), command.args());
+            println!("  {:<18} {}", synopsis.trim_end(), command.description());
+        }
+        println!("  Anything else is sent to the model.");
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Model;
+
+impl Command for Model {
+    fn name(&self) -> &str {
+        "model"
+    }
+
+    fn args(&self) -> &str {
+        "[name]"
+    }
+
+    fn description(&self) -> &str {
+        "Show or switch the model for the following turns"
+    }
+
+    fn run(&self, args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        if args.is_empty() {
+            println!("🤖 Model: {}", ctx.session.config.model);
+        } else {
+            ctx.session.config.model = args.to_string();
+            println!("🤖 Switched to {}", args);
+        }
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct System;
+
+impl Command for System {
+    fn name(&self) -> &str {
+        "system"
+    }
+
+    fn args(&self) -> &str {
+        "[prompt]"
+    }
+
+    fn description(&self) -> &str {
+        "Show or replace the system prompt"
+    }
+
+    fn run(&self, args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let conversation = &mut ctx.session.conversation;
+        if args.is_empty() {
+            println!("📝 {}", conversation.system_prompt().unwrap_or("(no system prompt)"));
+        } else {
+            conversation.set_system_prompt(args);
+            println!("📝 System prompt updated");
+        }
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Tools;
+
+impl Command for Tools {
+    fn name(&self) -> &str {
+        "tools"
+    }
+
+    fn description(&self) -> &str {
+        "List the registered tools and their parameter schemas"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        for definition in ctx.tools.definitions() {
+            let function = definition.function;
+            println!("🔧 {}: {}", function.name, function.description);
+            println!("{}", serde_json::to_string_pretty(&function.parameters)?);
+        }
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Clear;
+
+impl Command for Clear {
+    fn name(&self) -> &str {
+        "clear"
+    }
+
+    fn description(&self) -> &str {
+        "Start a new session with the same settings and system prompt"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let saved = ctx.session.conversation.has_user_messages();
+        if saved {
+            ctx.store.save(ctx.session)?;
+        }
+        let system_prompt = ctx.session.conversation.system_prompt().unwrap_or_default();
+        let session = Session::new(&ctx.session.config, Conversation::new(system_prompt));
+        if saved {
+            println!("🧹 Started session {}. The previous one was saved as {}", session.id, ctx.session.id);
+        } else {
+            println!("🧹 Started session {}", session.id);
+        }
+        *ctx.session = session;
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct History;
+
+impl Command for History {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn description(&self) -> &str {
+        "Show the messages in this session"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let conversation = &ctx.session.conversation;
+        for (index, message) in conversation.messages().iter().enumerate() {
+            let mut content: String = message.content.chars().take(200).collect();
+            if message.content.chars().count() > 200 {
+                content.push('…');
+            }
+            println!(
+                "{}[{}] {}: {}",
+                if conversation.is_pinned(index) { "📌 " } else { "" },
+                index,
+                message.role,
+                content
+            );
+            for tool_call in message.tool_calls.iter().flatten() {
+                println!("      🔧 {}({})", tool_call.function.name, tool_call.function.arguments);
+            }
+        }
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Save;
+
+impl Command for Save {
+    fn name(&self) -> &str {
+        "save"
+    }
+
+    fn description(&self) -> &str {
+        "Save this session now"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let path = ctx.store.save(ctx.session)?;
+        println!("💾 Saved session {} to {}", ctx.session.id, path.display());
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Load;
+
+impl Command for Load {
+    fn name(&self) -> &str {
+        "load"
+    }
+
+    fn args(&self) -> &str {
+        "<id>"
+    }
+
+    fn description(&self) -> &str {
+        "Save this session and switch to a saved one"
+    }
+
+    fn run(&self, args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let id = require(args, "/load <id>")?;
+        let mut session = ctx.store.load(id)?;
+        session.config = session.config.with_run_settings(&ctx.session.config);
+        if ctx.session.conversation.has_user_messages() {
+            ctx.store.save(ctx.session)?;
+        }
+        println!(
+            "📂 Loaded session {} ({} messages, model {})",
+            session.id,
+            session.conversation.len(),
+            session.config.model
+        );
+        *ctx.session = session;
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Usage;
+
+impl Command for Usage {
+    fn name(&self) -> &str {
+        "usage"
+    }
+
+    fn description(&self) -> &str {
+        "Show token usage and cost for this session"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let session = &ctx.session;
+        println!("📊 Session: {}", session.usage);
+        let history_tokens: u64 = session.conversation.messages().iter().map(estimate_tokens).sum();
+        match session.config.context.limit_for(&session.config.model) {
+            Some(limit) => println!(
+                "📊 History: {} messages, ~{} of {} context tokens",
+                session.conversation.len(),
+                history_tokens,
+                limit
+            ),
+            None => println!(
+                "📊 History: {} messages, ~{} tokens",
+                session.conversation.len(),
+                history_tokens
+            ),
+        }
+        if !session.conversation.compactions().is_empty() {
+            println!("🗜️ Compacted {} times", session.conversation.compactions().len());
+        }
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Retry;
+
+impl Command for Retry {
+    fn name(&self) -> &str {
+        "retry"
+    }
+
+    fn description(&self) -> &str {
+        "Send the last message again, after a failure or in place of its reply"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        // A failed turn was already dropped; the last completed one stays
+        if let Some(input) = ctx.session.failed_input.take() {
+            return Ok(CommandOutcome::Send(input));
+        }
+        let conversation = &mut ctx.session.conversation;
+        let Some(start) = conversation.last_turn_start() else {
+            bail!("nothing to retry yet");
+        };
+        let input = conversation.messages()[start].content.clone();
+        conversation.truncate(start);
+        Ok(CommandOutcome::Send(input))
+    }
+}
+
+pub struct Undo;
+
+impl Command for Undo {
+    fn name(&self) -> &str {
+        "undo"
+    }
+
+    fn description(&self) -> &str {
+        "Remove the last message and everything the model did in reply"
+    }
+
+    fn run(&self, _args: &str, ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        let conversation = &mut ctx.session.conversation;
+        let Some(start) = conversation.last_turn_start() else {
+            bail!("nothing to undo");
+        };
+        let removed = conversation.len() - start;
+        conversation.truncate(start);
+        println!("↩️ Removed the last turn ({} messages)", removed);
+        Ok(CommandOutcome::Continue)
+    }
+}
+
+pub struct Exit;
+
+impl Command for Exit {
+    fn name(&self) -> &str {
+        "exit"
+    }
+
+    fn description(&self) -> &str {
+        "Save the session and quit"
+    }
+
+    fn run(&self, _args: &str, _ctx: &mut CommandContext<'_>) -> Result<CommandOutcome> {
+        Ok(CommandOutcome::Exit)
+    }
+}
@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::agent::AgentLimits;
+use crate::cassette::CassetteConfig;
+use crate::context::{ContextConfig, ContextStrategy};
+use crate::rate_limit::RateLimitConfig;
+use crate::retry::RetryPolicy;
+use crate::telemetry::TelemetryConfig;
+use crate::usage::{default_price, ModelPrice};
+
+const DEFAULT_CONFIG_FILE: &str = "groq-agent.toml";
+const DEFAULT_SESSIONS_DIR: &str = ".groq-agent/sessions";
+
+/// Which API the agent talks to.
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Groq,
+    /// Any OpenAI-compatible server, selected with `base_url`
+    #[serde(alias = "openai-compatible")]
+    #[value(name = "openai", alias = "openai-compatible")]
+    OpenAi,
+    Anthropic,
+}
+
+/// Settings for talking to the model. Resolved from, in increasing order of
+/// precedence: built-in defaults, a TOML file, `GROQ_*` environment
+/// variables, and command-line flags.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub provider: ProviderKind,
+    pub model: String,
+    /// API root for the provider, e.g. `http://localhost:8080/v1` for a local
+    /// OpenAI-compatible server. Defaults to the provider's public endpoint.
+    pub base_url: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+    /// `auto`, `none`, `required`, or the name of a tool the model must call.
+    pub tool_choice: String,
+    pub stream: bool,
+    pub retry: RetryPolicy,
+    pub rate_limit: RateLimitConfig,
+    pub limits: AgentLimits,
+    pub context: ContextConfig,
+    // Where traces go and whether traffic is recorded or replayed belong to
+    // one run of the program, so sessions don't save them
+    #[serde(skip_serializing)]
+    pub telemetry: TelemetryConfig,
+    #[serde(skip_serializing)]
+    pub cassette: CassetteConfig,
+    /// Per-model prices for cost estimates, keyed by model name. Entries
+    /// here take precedence over the built-in table.
+    pub pricing: HashMap<String, ModelPrice>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            provider: ProviderKind::Groq,
+            model: "llama-3.3-70b-versatile".to_string(),
+            base_url: None,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            stop: None,
+            seed: None,
+            tool_choice: "auto".to_string(),
+            stream: false,
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimitConfig::default(),
+            limits: AgentLimits::default(),
+            context: ContextConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            cassette: CassetteConfig::default(),
+            pricing: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Interactive LLM agent with function calling")]
+pub struct Cli {
+    /// TOML config file (defaults to ./groq-agent.toml when present)
+    #[arg(long, env = "GROQ_AGENT_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// API to talk to
+    #[arg(long, env = "GROQ_PROVIDER", value_enum)]
+    pub provider: Option<ProviderKind>,
+    /// Model to use for every request
+    #[arg(long, env = "GROQ_MODEL")]
+    pub model: Option<String>,
+    /// OpenAI-compatible API root, e.g. http://localhost:8080/v1
+    #[arg(long, env = "GROQ_BASE_URL")]
+    pub base_url: Option<String>,
+    /// Sampling temperature
+    #[arg(long, env = "GROQ_TEMPERATURE")]
+    pub temperature: Option<f32>,
+    /// Nucleus sampling probability mass
+    #[arg(long, env = "GROQ_TOP_P")]
+    pub top_p: Option<f32>,
+    /// Maximum tokens to generate per reply
+    #[arg(long, env = "GROQ_MAX_TOKENS")]
+    pub max_tokens: Option<u32>,
+    /// Stop sequence; repeat the flag or comma-separate for several
+    #[arg(long, env = "GROQ_STOP", value_delimiter = ',')]
+    pub stop: Option<Vec<String>>,
+    /// Seed for reproducible sampling
+    #[arg(long, env = "GROQ_SEED")]
+    pub seed: Option<i64>,
+    /// auto, none, required, or a tool name to force
+    #[arg(long, env = "GROQ_TOOL_CHOICE")]
+    pub tool_choice: Option<String>,
+    /// Print replies token by token as they arrive
+    #[arg(long, env = "GROQ_STREAM", num_args = 0..=1, default_missing_value = "true")]
+    pub stream: Option<bool>,
+    /// Attempts per request, including the first, before giving up on
+    /// rate-limit, server and connection errors
+    #[arg(long, env = "GROQ_MAX_ATTEMPTS")]
+    pub max_attempts: Option<u32>,
+    /// Pace requests to at most this many per minute
+    #[arg(long, env = "GROQ_RPM")]
+    pub requests_per_minute: Option<u32>,
+    /// Pace requests to at most this many estimated tokens per minute
+    #[arg(long, env = "GROQ_TPM")]
+    pub tokens_per_minute: Option<u32>,
+    /// Tool round-trips allowed per turn before the model must answer
+    #[arg(long, env = "GROQ_MAX_TOOL_ROUNDS")]
+    pub max_tool_rounds: Option<u32>,
+    /// Wall-clock budget per turn, in seconds
+    #[arg(long, env = "GROQ_TURN_TIMEOUT")]
+    pub turn_timeout: Option<u64>,
+    /// How to fit long conversations into the model's context window
+    #[arg(long, env = "GROQ_CONTEXT_STRATEGY", value_enum)]
+    pub context_strategy: Option<ContextStrategy>,
+    /// Append tracing spans as JSON lines to this file
+    #[arg(long, env = "GROQ_TRACE_JSON")]
+    pub trace_json: Option<PathBuf>,
+    /// Export tracing spans to this OTLP/HTTP collector endpoint
+    #[arg(long, env = "GROQ_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+    /// Tracing filter directives, e.g. groq_agent=debug
+    #[arg(long, env = "GROQ_TRACE_FILTER")]
+    pub trace_filter: Option<String>,
+    /// Save every provider request and response to this cassette file
+    #[arg(long, env = "GROQ_RECORD_CASSETTE", conflicts_with = "replay_cassette")]
+    pub record_cassette: Option<PathBuf>,
+    /// Answer from a recorded cassette file instead of the network
+    #[arg(long, env = "GROQ_REPLAY_CASSETTE")]
+    pub replay_cassette: Option<PathBuf>,
+    /// Directory where sessions are saved
+    #[arg(long, env = "GROQ_SESSIONS_DIR", default_value = DEFAULT_SESSIONS_DIR)]
+    pub sessions_dir: PathBuf,
+    /// Resume a saved session by id
+    #[arg(long, value_name = "ID", conflicts_with = "continue_session")]
+    pub resume: Option<String>,
+    /// Resume the most recently updated session
+    #[arg(long = "continue")]
+    pub continue_session: bool,
+    /// List saved sessions and exit
+    #[arg(long)]
+    pub list_sessions: bool,
+}
+
+impl Config {
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+        config.apply_overrides(cli);
+        Ok(config)
+    }
+
+    /// Settings for a resumed session: the ones it was saved with, under any
+    /// flags and environment variables given now. Telemetry and cassettes
+    /// only come from the current config file and flags.
+    pub fn resume(saved: Config, cli: &Cli) -> Result<Self> {
+        let mut config = saved.with_run_settings(&Self::load(cli)?);
+        config.apply_overrides(cli);
+        Ok(config)
+    }
+
+    /// Replaces the settings that belong to a run of the program rather
+    /// than to a conversation with those of `current`, e.g. when switching
+    /// to a saved session.
+    pub fn with_run_settings(self, current: &Config) -> Self {
+        Config {
+            telemetry: current.telemetry.clone(),
+            cassette: current.cassette.clone(),
+            ..self
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    // Flags and environment variables are merged by clap, so one pass covers both
+    fn apply_overrides(&mut self, cli: &Cli) {
+        if let Some(provider) = cli.provider {
+            self.provider = provider;
+        }
+        if let Some(model) = &cli.model {
+            self.model = model.clone();
+        }
+        if cli.base_url.is_some() {
+            self.base_url = cli.base_url.clone();
+        }
+        if let Some(tool_choice) = &cli.tool_choice {
+            self.tool_choice = tool_choice.clone();
+        }
+        if let Some(stream) = cli.stream {
+            self.stream = stream;
+        }
+        self.temperature = cli.temperature.or(self.temperature);
+        self.top_p = cli.top_p.or(self.top_p);
+        self.max_tokens = cli.max_tokens.or(self.max_tokens);
+        self.seed = cli.seed.or(self.seed);
+        if cli.stop.is_some() {
+            self.stop = cli.stop.clone();
+        }
+        if let Some(max_attempts) = cli.max_attempts {
+            self.retry.max_attempts = max_attempts;
+        }
+        if cli.requests_per_minute.is_some() {
+            self.rate_limit.requests_per_minute = cli.requests_per_minute;
+        }
+        if cli.tokens_per_minute.is_some() {
+            self.rate_limit.tokens_per_minute = cli.tokens_per_minute;
+        }
+        if let Some(max_tool_rounds) = cli.max_tool_rounds {
+            self.limits.max_tool_rounds = max_tool_rounds;
+        }
+        if let Some(turn_timeout) = cli.turn_timeout {
+            self.limits.turn_timeout_secs = turn_timeout;
+        }
+        if let Some(context_strategy) = cli.context_strategy {
+            self.context.strategy = context_strategy;
+        }
+        if cli.trace_json.is_some() {
+            self.telemetry.json_log = cli.trace_json.clone();
+        }
+        if cli.otlp_endpoint.is_some() {
+            self.telemetry.otlp_endpoint = cli.otlp_endpoint.clone();
+        }
+        if let Some(trace_filter) = &cli.trace_filter {
+            self.telemetry.filter = trace_filter.clone();
+        }
+        if cli.record_cassette.is_some() {
+            self.cassette.record = cli.record_cassette.clone();
+        }
+        if cli.replay_cassette.is_some() {
+            self.cassette.replay = cli.replay_cassette.clone();
+        }
+    }
+
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        self.pricing
+            .get(model)
+            .copied()
+            .or_else(|| default_price(model))
+    }
+
+    /// The `tool_choice` request field: a mode string, or an object forcing a
+    /// specific tool.
+    pub fn tool_choice_value(&self) -> serde_json::Value {
+        match self.tool_choice.as_str() {
+            "auto" | "none" | "required" => serde_json::Value::from(self.tool_choice.as_str()),
+            name => serde_json::json!({ "type": "function", "function": { "name": name } }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumed_sessions_do_not_reuse_cassettes_or_trace_logs() {
+        let mut config = Config::default();
+        config.cassette.record = Some(PathBuf::from("recording.json"));
+        config.telemetry.json_log = Some(PathBuf::from("/elsewhere/traces.jsonl"));
+        let saved = serde_json::to_value(&config).unwrap();
+        assert!(saved.get("cassette").is_none() && saved.get("telemetry").is_none());
+
+        // Older session files may still carry them
+        let mut saved: Config = serde_json::from_value(saved).unwrap();
+        saved.cassette.record = Some(PathBuf::from("recording.json"));
+        saved.telemetry.json_log = Some(PathBuf::from("/elsewhere/traces.jsonl"));
+
+        let cli = Cli::parse_from(["groq-rust-agent", "--replay-cassette", "replay.json"]);
+        let resumed = Config::resume(saved, &cli).unwrap();
+        assert_eq!(resumed.cassette.record, None);
+        assert_eq!(resumed.cassette.replay, Some(PathBuf::from("replay.json")));
+        assert_eq!(resumed.telemetry.json_log, None);
+    }
+}
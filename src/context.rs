@@ -0,0 +1,393 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::agent::send_chat_request;
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::events::EventBus;
+use crate::providers::LlmProvider;
+use crate::session::unix_now;
+use crate::usage::Usage;
+use crate::{ChatRequest, Conversation, Message, ToolDefinition};
+
+// Fixed cost of a message's role and framing on top of its content
+const MESSAGE_OVERHEAD_TOKENS: u64 = 4;
+// Headroom for the reply when `max_tokens` isn't configured
+const DEFAULT_REPLY_RESERVE_TOKENS: u64 = 1024;
+
+const SUMMARY_PROMPT: &str = "You compress conversation histories. Summarize the conversation you are given into a concise note for the assistant that continues it. Keep facts the user shared, decisions made, tool results that may matter later, and open questions. Reply with the summary only.";
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
+/// How the history is cut down when it no longer fits the context window.
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Send everything and let the provider reject oversized requests.
+    None,
+    /// Drop whole turns, oldest first, keeping system and pinned messages.
+    #[default]
+    DropOldest,
+    /// Ask the model to fold the oldest turns into a summary note once the
+    /// history passes `summarize_threshold` of the budget. Falls back to
+    /// dropping turns if the history still doesn't fit.
+    Summarize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub strategy: ContextStrategy,
+    /// Fraction of the message budget at which summarization kicks in.
+    pub summarize_threshold: f64,
+    /// Most recent turns that are never summarized, including the current one.
+    pub keep_recent_turns: usize,
+    /// Context window sizes in tokens, keyed by model name. Entries here
+    /// take precedence over the built-in table.
+    pub limits: HashMap<String, u64>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        ContextConfig {
+            strategy: ContextStrategy::DropOldest,
+            summarize_threshold: 0.8,
+            keep_recent_turns: 2,
+            limits: HashMap::new(),
+        }
+    }
+}
+
+/// A record of the history being summarized, kept with the conversation.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Compaction {
+    /// Unix time, in seconds, when the compaction happened.
+    pub timestamp: u64,
+    pub messages_summarized: usize,
+    pub tokens_before: u64,
+    pub tokens_after: u64,
+}
+
+impl ContextConfig {
+    pub fn limit_for(&self, model: &str) -> Option<u64> {
+        self.limits
+            .get(model)
+            .copied()
+            .or_else(|| default_context_limit(model))
+    }
+
+    pub fn policy(&self) -> Box<dyn TrimPolicy> {
+        match self.strategy {
+            ContextStrategy::None => Box::new(KeepAll),
+            ContextStrategy::DropOldest | ContextStrategy::Summarize => Box::new(DropOldestTurns),
+        }
+    }
+}
+
+/// Context window sizes of well-known models.
+pub fn default_context_limit(model: &str) -> Option<u64> {
+    let limit = match model {
+        "llama-3.3-70b-versatile" | "llama-3.1-8b-instant" => 131_072,
+        "gemma2-9b-it" => 8_192,
+        model if model.starts_with("claude-") => 200_000,
+        model if model.starts_with("gpt-4o") || model.starts_with("gpt-4.1") => 128_000,
+        _ => return None,
+    };
+    Some(limit)
+}
+
+/// Approximate token count of a message: about four characters per token,
+/// which is close enough for budgeting without shipping a tokenizer.
+pub fn estimate_tokens(message: &Message) -> u64 {
+    let mut chars = message.content.chars().count();
+    for tool_call in message.tool_calls.iter().flatten() {
+        chars += tool_call.id.len() + tool_call.function.name.len() + tool_call.function.arguments.len();
+    }
+    if let Some(tool_call_id) = &message.tool_call_id {
+        chars += tool_call_id.len();
+    }
+    MESSAGE_OVERHEAD_TOKENS + (chars as u64).div_ceil(4)
+}
+
+pub fn estimate_tools_tokens(tools: &[ToolDefinition]) -> u64 {
+    serde_json::to_string(tools).map_or(0, |json| json.len() as u64 / 4)
+}
+
+/// Tokens available for messages in a request: the model's window minus the
+/// tool definitions and room for the reply. `None` when the model's window
+/// is unknown.
+pub fn message_budget(
+    context: &ContextConfig,
+    model: &str,
+    tools: &[ToolDefinition],
+    max_tokens: Option<u32>,
+) -> Option<u64> {
+    let reply = max_tokens.map_or(DEFAULT_REPLY_RESERVE_TOKENS, u64::from);
+    context
+        .limit_for(model)
+        .map(|limit| limit.saturating_sub(estimate_tools_tokens(tools) + reply))
+}
+
+/// Chooses which messages of a conversation to send when they don't all fit
+/// in `budget` tokens.
+pub trait TrimPolicy: Send + Sync {
+    fn trim(&self, conversation: &Conversation, budget: u64) -> Vec<Message>;
+}
+
+/// Sends the full history regardless of size.
+pub struct KeepAll;
+
+impl TrimPolicy for KeepAll {
+    fn trim(&self, conversation: &Conversation, _budget: u64) -> Vec<Message> {
+        conversation.messages().to_vec()
+    }
+}
+
+/// Drops the oldest turns (a user message and everything up to the next
+/// one) until the rest fits. Leading system messages, turns holding a pinned
+/// message, and the current turn are always kept. Because whole turns go at
+/// once, an assistant's `tool_calls` never lose their `tool` results.
+pub struct DropOldestTurns;
+
+impl TrimPolicy for DropOldestTurns {
+    fn trim(&self, conversation: &Conversation, budget: u64) -> Vec<Message> {
+        let messages = conversation.messages();
+        let mut total: u64 = messages.iter().map(estimate_tokens).sum();
+        if total <= budget {
+            return messages.to_vec();
+        }
+
+        let turns = split_turns(messages);
+        let mut dropped = vec![false; turns.len()];
+        // The last turn is the one being answered
+        for (index, turn) in turns.iter().enumerate().take(turns.len().saturating_sub(1)) {
+            if total <= budget {
+                break;
+            }
+            let is_leading_system = turn.start == 0 && messages[0].role == "system";
+            if is_leading_system || turn.clone().any(|i| conversation.is_pinned(i)) {
+                continue;
+            }
+            total -= messages[turn.clone()].iter().map(estimate_tokens).sum::<u64>();
+            dropped[index] = true;
+        }
+
+        turns
+            .into_iter()
+            .zip(dropped)
+            .filter(|(_, dropped)| !dropped)
+            .flat_map(|(turn, _)| messages[turn].iter().cloned())
+            .collect()
+    }
+}
+
+/// Replaces the oldest turns with a model-written summary once the history
+/// passes the summarization threshold. Leading system messages, pinned turns
+/// and the `keep_recent_turns` most recent turns stay verbatim, and an
+/// earlier summary is folded into the new one. The compaction is recorded on
+/// the conversation. The request is reported on `events` like any other,
+/// but never streamed. Returns the usage of the summarization request, or
+/// `None` when nothing needed compacting.
+pub async fn summarize_oldest(
+    provider: &dyn LlmProvider,
+    config: &Config,
+    conversation: &mut Conversation,
+    budget: u64,
+    events: &EventBus,
+) -> Result<Option<Usage>, ApiError> {
+    let messages = conversation.messages();
+    let tokens_before: u64 = messages.iter().map(estimate_tokens).sum();
+    if (tokens_before as f64) < budget as f64 * config.context.summarize_threshold {
+        return Ok(None);
+    }
+
+    let turns = split_turns(messages);
+    let keep_from = turns.len().saturating_sub(config.context.keep_recent_turns.max(1));
+    // The leading block holds the system prompt and any earlier summary;
+    // split it so the summary is folded in while the prompt stays
+    let summarized: BTreeSet<usize> = turns[..keep_from]
+        .iter()
+        .flat_map(|turn| {
+            if messages[turn.start].role == "user" {
+                vec![turn.clone()]
+            } else {
+                turn.clone().map(|index| index..index + 1).collect()
+            }
+        })
+        .filter(|unit| {
+            let is_system_prompt = unit.start == 0 && messages[0].role == "system";
+            !is_system_prompt && !unit.clone().any(|index| conversation.is_pinned(index))
+        })
+        .flatten()
+        .collect();
+    if summarized.len() < 2 {
+        return Ok(None);
+    }
+
+    let transcript = summarized
+        .iter()
+        .map(|&index| render_message(&messages[index]))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let request = ChatRequest::new(
+        config,
+        vec![
+            Message::new("system", SUMMARY_PROMPT),
+            Message::new("user", transcript),
+        ],
+        Vec::new(),
+    );
+    let response = send_chat_request(provider, &request, false, events).await?;
+    let usage = response.usage();
+    let summary = response
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.map(|message| message.content).or(choice.text))
+        .filter(|summary| !summary.trim().is_empty())
+        .ok_or_else(|| ApiError::Decode("the model returned an empty summary".to_string()))?;
+
+    let messages_summarized = summarized.len();
+    conversation.compact(
+        &summarized,
+        Message::new("system", format!("{}\n{}", SUMMARY_PREFIX, summary.trim())),
+    );
+    let tokens_after: u64 = conversation.messages().iter().map(estimate_tokens).sum();
+    conversation.record_compaction(Compaction {
+        timestamp: unix_now(),
+        messages_summarized,
+        tokens_before,
+        tokens_after,
+    });
+    Ok(Some(usage))
+}
+
+fn render_message(message: &Message) -> String {
+    let mut rendered = format!("{}: {}", message.role, message.content);
+    for tool_call in message.tool_calls.iter().flatten() {
+        rendered.push_str(&format!(
+            "\n[called {} with {}]",
+            tool_call.function.name, tool_call.function.arguments
+        ));
+    }
+    rendered
+}
+
+// Index ranges of the leading system block and of each turn that follows,
+// where a turn starts at a user message.
+fn split_turns(messages: &[Message]) -> Vec<std::ops::Range<usize>> {
+    let mut turns = Vec::new();
+    let mut start = 0;
+    for (index, message) in messages.iter().enumerate().skip(1) {
+        if message.role == "user" {
+            turns.push(start..index);
+            start = index;
+        }
+    }
+    if start < messages.len() {
+        turns.push(start..messages.len());
+    }
+    turns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FunctionCall, ToolCall};
+
+    fn assistant_calling(id: &str) -> (Message, ToolCall) {
+        let tool_call = ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: FunctionCall {
+                name: "calculate".to_string(),
+                arguments: r#"{"a":1,"b":2,"operation":"+"}"#.to_string(),
+            },
+        };
+        let mut message = Message::new("assistant", "");
+        message.tool_calls = Some(vec![tool_call.clone()]);
+        (message, tool_call)
+    }
+
+    // system, a tool-calling turn, a pinned turn, a plain turn, then the
+    // turn being answered
+    fn conversation() -> Conversation {
+        let mut conversation = Conversation::new("You are terse.");
+        conversation.push(Message::new("user", "what is 1 + 2?"));
+        let (call, tool_call) = assistant_calling("call_1");
+        conversation.push(call);
+        conversation.push(Message::tool(&tool_call, "The result of 1 + 2 is 3"));
+        conversation.push(Message::new("assistant", "3"));
+        conversation.push_pinned(Message::new("user", "remember: my name is Sam"));
+        conversation.push(Message::new("assistant", "Noted."));
+        conversation.push(Message::new("user", "tell me a long story"));
+        conversation.push(Message::new("assistant", "Once upon a time ".repeat(50)));
+        conversation.push(Message::new("user", "and now a short one"));
+        conversation
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|message| message.content.as_str()).collect()
+    }
+
+    fn total(messages: &[Message]) -> u64 {
+        messages.iter().map(estimate_tokens).sum()
+    }
+
+    #[test]
+    fn splits_turns_at_user_messages() {
+        let conversation = conversation();
+        assert_eq!(split_turns(conversation.messages()), [0..1, 1..5, 5..7, 7..9, 9..10]);
+    }
+
+    #[test]
+    fn keeps_everything_that_fits() {
+        let conversation = conversation();
+        let budget = total(conversation.messages());
+        assert_eq!(DropOldestTurns.trim(&conversation, budget).len(), conversation.len());
+    }
+
+    #[test]
+    fn drops_oldest_unpinned_turns_first() {
+        let conversation = conversation();
+        let messages = conversation.messages();
+        // Just enough room once the tool-calling turn is gone
+        let budget = total(messages) - total(&messages[1..5]);
+
+        let trimmed = DropOldestTurns.trim(&conversation, budget);
+
+        assert_eq!(trimmed.len(), conversation.len() - 4);
+        assert_eq!(trimmed[0].content, "You are terse.");
+        assert_eq!(trimmed[1].content, "remember: my name is Sam");
+    }
+
+    #[test]
+    fn keeps_system_prompt_pinned_and_current_turns_over_budget() {
+        let conversation = conversation();
+
+        let trimmed = DropOldestTurns.trim(&conversation, 0);
+
+        assert_eq!(
+            contents(&trimmed),
+            ["You are terse.", "remember: my name is Sam", "Noted.", "and now a short one"]
+        );
+    }
+
+    #[test]
+    fn never_separates_tool_calls_from_their_results() {
+        let conversation = conversation();
+        for budget in 0..=total(conversation.messages()) {
+            let trimmed = DropOldestTurns.trim(&conversation, budget);
+            let calls: Vec<&str> = trimmed
+                .iter()
+                .flat_map(|message| message.tool_calls.iter().flatten())
+                .map(|tool_call| tool_call.id.as_str())
+                .collect();
+            let results: Vec<&str> = trimmed
+                .iter()
+                .filter_map(|message| message.tool_call_id.as_deref())
+                .collect();
+            assert_eq!(calls, results, "budget {}", budget);
+        }
+    }
+}
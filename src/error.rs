@@ -0,0 +1,218 @@
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+use std::fmt;
+use std::time::Duration;
+
+use crate::retry::retry_after_from_headers;
+
+/// Why a request to the model failed.
+#[derive(Debug)]
+pub enum ApiError {
+    /// Missing, invalid, or unauthorized API key (401/403).
+    Auth { message: String },
+    /// Too many requests or tokens for the current quota (429).
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The conversation no longer fits in the model's context window.
+    ContextLength { message: String },
+    /// The request was rejected as malformed or unsupported (other 4xx).
+    InvalidRequest { status: StatusCode, message: String },
+    /// The provider failed or is overloaded (5xx).
+    Server {
+        status: StatusCode,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The request never got a response, e.g. DNS, TLS or connection errors.
+    Transport(reqwest::Error),
+    /// A successful response whose body didn't have the expected shape.
+    Decode(String),
+}
+
+// `{"error": {"message": ..., "type": ..., "code": ...}}`, shared by
+// OpenAI-compatible APIs and Anthropic
+#[derive(Deserialize, Debug)]
+pub(crate) struct ErrorEnvelope {
+    pub(crate) error: ErrorBody,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ErrorBody {
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "type", default)]
+    error_type: Option<String>,
+    #[serde(default)]
+    code: Option<serde_json::Value>,
+}
+
+impl ErrorBody {
+    fn is_context_length(&self) -> bool {
+        let code = self.code.as_ref().and_then(|code| code.as_str());
+        if code == Some("context_length_exceeded") {
+            return true;
+        }
+        let message = self.message.to_lowercase();
+        ["context length", "context window", "maximum context", "prompt is too long"]
+            .iter()
+            .any(|needle| message.contains(needle))
+    }
+
+    fn describe(&self) -> String {
+        match &self.error_type {
+            Some(error_type) if !self.message.is_empty() => {
+                format!("{} ({})", self.message, error_type)
+            }
+            _ => self.message.clone(),
+        }
+    }
+}
+
+impl ApiError {
+    /// Classifies a non-success response from its status and error body.
+    pub fn from_status(status: StatusCode, body: &str) -> Self {
+        match serde_json::from_str::<ErrorEnvelope>(body) {
+            Ok(envelope) => Self::classify(
+                status,
+                envelope.error.describe(),
+                envelope.error.is_context_length(),
+            ),
+            Err(_) if body.trim().is_empty() => Self::classify(status, status.to_string(), false),
+            Err(_) => Self::classify(status, body.trim().to_string(), false),
+        }
+    }
+
+    /// Attaches the server's requested wait to rate-limit and server errors.
+    pub fn with_retry_after(mut self, delay: Option<Duration>) -> Self {
+        if let ApiError::RateLimited { retry_after, .. } | ApiError::Server { retry_after, .. } =
+            &mut self
+        {
+            *retry_after = delay;
+        }
+        self
+    }
+
+    /// Short machine-readable category, for logs and traces.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ApiError::Auth { .. } => "auth",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::ContextLength { .. } => "context_length",
+            ApiError::InvalidRequest { .. } => "invalid_request",
+            ApiError::Server { .. } => "server",
+            ApiError::Transport(_) => "transport",
+            ApiError::Decode(_) => "decode",
+        }
+    }
+
+    /// Whether sending the same request again could succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::RateLimited { .. } | ApiError::Server { .. } => true,
+            // Connection refused/reset and timeouts, but not malformed requests
+            ApiError::Transport(e) => !e.is_builder() && !e.is_redirect(),
+            _ => false,
+        }
+    }
+
+    /// The wait the server asked for, if any.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ApiError::RateLimited { retry_after, .. } | ApiError::Server { retry_after, .. } => {
+                *retry_after
+            }
+            _ => None,
+        }
+    }
+
+    /// An error reported in-band, such as an `error` event in a stream, where
+    /// the HTTP status was already 200.
+    pub(crate) fn from_envelope(envelope: ErrorEnvelope) -> Self {
+        let status = match envelope.error.error_type.as_deref() {
+            Some("rate_limit_error" | "rate_limit_exceeded") => StatusCode::TOO_MANY_REQUESTS,
+            Some("authentication_error") => StatusCode::UNAUTHORIZED,
+            Some("invalid_request_error") => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::classify(status, envelope.error.describe(), envelope.error.is_context_length())
+    }
+
+    fn classify(status: StatusCode, message: String, context_length: bool) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ApiError::Auth { message },
+            StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+                message,
+                retry_after: None,
+            },
+            _ if context_length => ApiError::ContextLength { message },
+            // 529 is Anthropic's "overloaded"
+            _ if status.is_server_error() || status.as_u16() == 529 => ApiError::Server {
+                status,
+                message,
+                retry_after: None,
+            },
+            _ => ApiError::InvalidRequest { status, message },
+        }
+    }
+
+    /// Passes successful responses through and turns everything else into
+    /// the matching `ApiError`.
+    pub async fn check_response(response: Response) -> Result<Response, ApiError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = retry_after_from_headers(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        Err(ApiError::from_status(status, &body).with_retry_after(retry_after))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Auth { message } => write!(f, "Authentication failed: {}", message),
+            ApiError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            ApiError::ContextLength { message } => {
+                write!(f, "Context length exceeded: {}", message)
+            }
+            ApiError::InvalidRequest { status, message } => {
+                write!(f, "Invalid request ({}): {}", status, message)
+            }
+            ApiError::Server {
+                status, message, ..
+            } => {
+                write!(f, "Server error ({}): {}", status, message)
+            }
+            ApiError::Transport(e) => write!(f, "Request failed: {}", e),
+            ApiError::Decode(message) => write!(f, "Unexpected response: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            ApiError::Decode(e.to_string())
+        } else {
+            ApiError::Transport(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Decode(e.to_string())
+    }
+}
@@ -0,0 +1,171 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::context::Compaction;
+use crate::usage::{Usage, UsageTotals};
+use crate::ToolCall;
+
+/// Something that happened while the agent answered. Subscribe with
+/// `AgentBuilder::subscribe` to drive a UI, log, or collect metrics.
+#[derive(Debug, Clone)]
+pub enum AgentEvent {
+    /// A chat request is about to go to the provider.
+    RequestSent { model: String, messages: usize },
+    /// A fragment of a streamed reply.
+    TokenDelta(String),
+    /// The model asked for a tool call; it runs after every call of the
+    /// round has been announced.
+    ToolCallRequested(ToolCall),
+    /// Arguments were valid and the tool is running.
+    ToolStarted { call_id: String, name: String },
+    ToolFinished { call_id: String, name: String, output: String },
+    /// The tool is unknown, its arguments were rejected, or it failed. The
+    /// model receives `error` as the tool result.
+    ToolFailed { call_id: String, name: String, error: String },
+    /// A tool limit ended the loop; the model must answer without tools.
+    ToolLimitReached { reason: String },
+    /// The model's reply once it stopped calling tools.
+    FinalAnswer { content: String },
+    /// Tokens used by one request, and by the turn so far.
+    Usage { request: Usage, turn: UsageTotals },
+    /// A request failed and will be sent again after `delay`.
+    Retrying { error: String, delay: Duration, attempt: u32, max_attempts: u32 },
+    /// The client is holding a request back to stay within rate limits.
+    Pacing { wait: Duration },
+    /// Older messages were left out of a request to fit the context window.
+    ContextTrimmed { dropped_messages: usize },
+    /// The oldest history was replaced by a summary.
+    ContextCompacted(Compaction),
+    /// Summarizing failed; older turns are dropped instead.
+    SummarizationFailed { error: String },
+}
+
+/// Receives agent events. Implemented for closures and for channel senders,
+/// so `subscribe(|event: &AgentEvent| ...)` and `subscribe(tx)` both work.
+pub trait EventHandler: Send + Sync {
+    fn handle(&self, event: &AgentEvent);
+}
+
+impl<F: Fn(&AgentEvent) + Send + Sync> EventHandler for F {
+    fn handle(&self, event: &AgentEvent) {
+        self(event)
+    }
+}
+
+impl EventHandler for UnboundedSender<AgentEvent> {
+    fn handle(&self, event: &AgentEvent) {
+        // A dropped receiver just means nobody is listening any more
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Fans events out to every subscriber, in subscription order.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    handlers: Vec<Arc<dyn EventHandler>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&mut self, handler: impl EventHandler + 'static) {
+        self.handlers.push(Arc::new(handler));
+    }
+
+    pub fn emit(&self, event: AgentEvent) {
+        for handler in &self.handlers {
+            handler.handle(&event);
+        }
+    }
+}
+
+/// Prints events as the interactive REPL's status lines.
+#[derive(Default)]
+pub struct ConsolePrinter {
+    state: Mutex<PrinterState>,
+}
+
+#[derive(Default)]
+struct PrinterState {
+    // A streamed reply is mid-line
+    mid_line: bool,
+    // The latest response was streamed, so its answer is already on screen
+    streamed: bool,
+}
+
+impl ConsolePrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EventHandler for ConsolePrinter {
+    fn handle(&self, event: &AgentEvent) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let AgentEvent::TokenDelta(token) = event {
+            if !state.mid_line {
+                print!("\n🤖 Chatbot: ");
+                state.mid_line = true;
+                state.streamed = true;
+            }
+            print!("{}", token);
+            let _ = io::stdout().flush();
+            return;
+        }
+        if std::mem::take(&mut state.mid_line) {
+            println!();
+        }
+
+        match event {
+            AgentEvent::ToolCallRequested(tool_call) => {
+                println!("\n🤖 Model requested function: {}", tool_call.function.name);
+                println!("📥 With parameters: {}", tool_call.function.arguments);
+            }
+            AgentEvent::ToolStarted { call_id, name } => {
+                println!("🔧 Function '{}' called ({})", name, call_id);
+            }
+            AgentEvent::ToolFinished { output, .. } => {
+                println!("📤 Function output: {}", output);
+                println!("✅ Function executed successfully");
+            }
+            AgentEvent::ToolFailed { error, .. } => println!("❌ Function error: {}", error),
+            AgentEvent::ToolLimitReached { reason } => {
+                println!("⚠️ Tool limit reached: {}. Asking for a final answer.", reason)
+            }
+            AgentEvent::RequestSent { .. } => state.streamed = false,
+            AgentEvent::FinalAnswer { content } if !state.streamed => {
+                println!("\n🤖 Chatbot: {}", content)
+            }
+            AgentEvent::Retrying { error, delay, attempt, max_attempts } => println!(
+                "⏳ {} — retrying in {:.1}s (attempt {}/{})",
+                error,
+                delay.as_secs_f64(),
+                attempt,
+                max_attempts
+            ),
+            AgentEvent::Pacing { wait } => println!(
+                "⏳ Pacing requests: waiting {:.1}s for rate limit budget",
+                wait.as_secs_f64()
+            ),
+            AgentEvent::ContextTrimmed { dropped_messages } => println!(
+                "✂️ Trimmed {} older message{} to fit the context window",
+                dropped_messages,
+                if *dropped_messages == 1 { "" } else { "s" }
+            ),
+            AgentEvent::ContextCompacted(compaction) => println!(
+                "🗜️ Summarized {} older messages (~{} → ~{} tokens)",
+                compaction.messages_summarized, compaction.tokens_before, compaction.tokens_after
+            ),
+            AgentEvent::SummarizationFailed { error } => println!(
+                "⚠️ Could not summarize the history ({}); dropping older turns instead",
+                error
+            ),
+            _ => {}
+        }
+    }
+}
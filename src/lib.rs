@@ -0,0 +1,338 @@
+//! An LLM agent with function calling. Build an [`Agent`] from a provider,
+//! a [`ToolRegistry`] and a system prompt, then `run` user input through the
+//! tool loop, or drive a [`Conversation`] you manage yourself with `chat`.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeSet;
+
+pub mod agent;
+pub mod cassette;
+pub mod commands;
+pub mod config;
+pub mod context;
+pub mod error;
+pub mod events;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod providers;
+pub mod rate_limit;
+pub mod retry;
+pub mod session;
+pub mod stream;
+pub mod telemetry;
+pub mod tools;
+pub mod transport;
+pub mod usage;
+
+pub use agent::{Agent, AgentBuilder, AgentLimits};
+pub use config::Config;
+pub use context::Compaction;
+pub use error::ApiError;
+pub use events::{AgentEvent, ConsolePrinter, EventBus, EventHandler};
+pub use providers::LlmProvider;
+pub use tools::{Tool, ToolArgs, ToolContext, ToolError, ToolOutput, ToolRegistry, TypedTool};
+pub use usage::{GroqMetadata, Usage, UsageTotals};
+
+// Lets `#[derive(ToolArgs)]` expansions name this crate as `::groq_agent`.
+extern crate self as groq_agent;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Message {
+    pub role: String,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_type")]
+    pub call_type: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    // JSON-encoded arguments, exactly as the model produced them
+    pub arguments: String,
+}
+
+pub(crate) fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+// Models return `"content": null` alongside native tool calls
+fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+impl Message {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Message {
+            role: role.to_string(),
+            content: content.into(),
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    // Result of a single tool call, linked back to the assistant's request
+    pub fn tool(tool_call: &ToolCall, content: impl Into<String>) -> Self {
+        Message {
+            tool_call_id: Some(tool_call.id.clone()),
+            name: Some(tool_call.function.name.clone()),
+            ..Message::new("tool", content)
+        }
+    }
+}
+
+/// Full message history of one chat, replayed on every request so the model
+/// sees earlier turns and tool round-trips.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Conversation {
+    messages: Vec<Message>,
+    // Indices of messages that context trimming must never drop
+    pinned: BTreeSet<usize>,
+    compactions: Vec<Compaction>,
+}
+
+impl Conversation {
+    pub fn new(system_prompt: &str) -> Self {
+        let mut conversation = Conversation {
+            messages: Vec::new(),
+            pinned: BTreeSet::new(),
+            compactions: Vec::new(),
+        };
+        conversation.push_pinned(Message::new("system", system_prompt));
+        conversation
+    }
+
+    pub fn push(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    pub fn push_pinned(&mut self, message: Message) {
+        self.pinned.insert(self.messages.len());
+        self.messages.push(message);
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.messages
+            .first()
+            .filter(|message| message.role == "system")
+            .map(|message| message.content.as_str())
+    }
+
+    pub fn set_system_prompt(&mut self, prompt: &str) {
+        match self.messages.first_mut() {
+            Some(message) if message.role == "system" => message.content = prompt.to_string(),
+            _ => {
+                self.messages.insert(0, Message::new("system", prompt));
+                self.pinned = self.pinned.iter().map(|index| index + 1).collect();
+                self.pinned.insert(0);
+            }
+        }
+    }
+
+    pub fn has_user_messages(&self) -> bool {
+        self.messages.iter().any(|message| message.role == "user")
+    }
+
+    /// Index of the user message that started the latest turn.
+    pub fn last_turn_start(&self) -> Option<usize> {
+        self.messages.iter().rposition(|message| message.role == "user")
+    }
+
+    pub fn is_pinned(&self, index: usize) -> bool {
+        self.pinned.contains(&index)
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.messages.truncate(len);
+        self.pinned.retain(|&index| index < len);
+    }
+
+    /// Replaces the `summarized` messages with `summary`, placed where the
+    /// first of them was.
+    pub fn compact(&mut self, summarized: &BTreeSet<usize>, summary: Message) {
+        let Some(&first) = summarized.first() else {
+            return;
+        };
+        let mut summary = Some(summary);
+        let mut messages = Vec::with_capacity(self.messages.len() - summarized.len() + 1);
+        let mut pinned = BTreeSet::new();
+        for (index, message) in self.messages.drain(..).enumerate() {
+            if index == first {
+                messages.extend(summary.take());
+            }
+            if summarized.contains(&index) {
+                continue;
+            }
+            if self.pinned.contains(&index) {
+                pinned.insert(messages.len());
+            }
+            messages.push(message);
+        }
+        self.messages = messages;
+        self.pinned = pinned;
+    }
+
+    pub fn record_compaction(&mut self, compaction: Compaction) {
+        self.compactions.push(compaction);
+    }
+
+    pub fn compactions(&self) -> &[Compaction] {
+        &self.compactions
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolFunction {
+    pub name: String,
+    pub description: String,
+    pub parameters: ToolFunctionParameters,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolFunctionParameters {
+    #[serde(rename = "type")]
+    pub param_type: String,
+    pub properties: serde_json::Value,
+    pub required: Vec<String>,
+}
+
+impl ToolFunctionParameters {
+    pub fn object(properties: serde_json::Value, required: Vec<String>) -> Self {
+        ToolFunctionParameters {
+            param_type: "object".to_string(),
+            properties,
+            required,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChatRequest {
+    pub model: String,
+    pub messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "serde_json::Value::is_null")]
+    pub tool_choice: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+}
+
+impl ChatRequest {
+    /// `tool_choice` is only set when there are tools; OpenAI rejects it
+    /// on its own.
+    pub fn new(config: &Config, messages: Vec<Message>, tools: Vec<ToolDefinition>) -> Self {
+        let tool_choice = if tools.is_empty() {
+            serde_json::Value::Null
+        } else {
+            config.tool_choice_value()
+        };
+        ChatRequest {
+            model: config.model.clone(),
+            messages,
+            tools,
+            tool_choice,
+            stream: None,
+            stream_options: None,
+            temperature: config.temperature,
+            top_p: config.top_p,
+            max_tokens: config.max_tokens,
+            stop: config.stop.clone(),
+            seed: config.seed,
+        }
+    }
+}
+
+/// OpenAI only sends usage for a streamed reply, in a final chunk, when
+/// `include_usage` is set.
+#[derive(Serialize, Debug, Clone)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ToolDefinition {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: ToolFunction,
+}
+
+impl ToolDefinition {
+    pub fn from_tool(tool: &dyn Tool) -> Self {
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: ToolFunction {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                parameters: tool.parameters_schema(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChatResponse {
+    pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    #[serde(default)]
+    pub x_groq: Option<GroqMetadata>,
+}
+
+impl ChatResponse {
+    /// Token usage for this request, wherever the provider reported it.
+    pub fn usage(&self) -> Usage {
+        self.usage
+            .clone()
+            .or_else(|| self.x_groq.as_ref().and_then(|x_groq| x_groq.usage.clone()))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Choice {
+    pub message: Option<Message>,
+    pub text: Option<String>,
+}
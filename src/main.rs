@@ -1,233 +1,164 @@
 use anyhow::Result;
+use clap::Parser;
 use dotenv::dotenv;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::env;
-use std::io::{self, Write};
-use regex::Regex;
+use groq_agent::commands::{CommandContext, CommandOutcome, CommandRegistry};
+use groq_agent::config::{Cli, Config};
+use groq_agent::session::{Session, SessionStore};
+use groq_agent::tools::Calculate;
+use groq_agent::{Agent, ApiError, ConsolePrinter, Message, ToolRegistry};
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::io::{self, Write};
 
 lazy_static! {
-    static ref FUNCTION_REGEX: Regex = Regex::new(r"<function=(\w+)(\{.*?\})>").unwrap();
-    static ref FUNCTION_REGISTRY: HashMap<&'static str, FunctionHandler> = {
-        let mut m = HashMap::new();
-        m.insert("calculate", handle_calculate as FunctionHandler);
-        m
-    };
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Message {
-    role: String,
-    #[serde(default)]
-    content: String,
-}
-
-#[derive(Serialize, Debug, Clone)]
-struct ToolFunction {
-    name: String,
-    description: String,
-    parameters: ToolFunctionParameters,
-}
-
-#[derive(Serialize, Debug, Clone)]
-struct ToolFunctionParameters {
-    #[serde(rename = "type")]
-    param_type: String,
-    properties: serde_json::Value,
-    required: Vec<String>,
+    static ref FUNCTION_REGISTRY: ToolRegistry = ToolRegistry::new().register(Calculate);
+    static ref COMMAND_REGISTRY: CommandRegistry = CommandRegistry::builtin();
 }
 
-#[derive(Serialize, Debug, Clone)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<Message>,
-    tools: Vec<Tool>,
-    tool_choice: String,
-}
-
-#[derive(Serialize, Debug, Clone)]
-struct Tool {
-    #[serde(rename = "type")]
-    tool_type: String,
-    function: ToolFunction,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-struct ChatResponse {
-    choices: Vec<Choice>,
-}
-
-#[derive(Deserialize, Debug, Clone)]
-struct Choice {
-    message: Option<Message>,
-    text: Option<String>,
+fn report_error(error: &anyhow::Error) {
+    println!("\n❌ {}", error);
+    let hint = match error.downcast_ref::<ApiError>() {
+        Some(ApiError::Auth { .. }) => "Check the API key for the configured provider.",
+        Some(ApiError::RateLimited { .. }) => "Wait a moment before sending another message.",
+        Some(ApiError::ContextLength { .. }) => {
+            "The conversation is too long for this model; start a new session or use a model with a larger context."
+        }
+        Some(ApiError::Server { .. }) => "The provider is having trouble; try again shortly.",
+        _ => return,
+    };
+    println!("💡 {}", hint);
 }
 
-// Define function type
-type FunctionHandler = fn(serde_json::Value) -> String;
-
-fn handle_calculate(params: serde_json::Value) -> String {
-    println!("\n🔧 Function 'calculate' called with parameters: {}", params);
-    if let (Some(a), Some(b), Some(op)) = (
-        params.get("a").and_then(|v| v.as_f64()),
-        params.get("b").and_then(|v| v.as_f64()),
-        params.get("operation").and_then(|v| v.as_str()),
-    ) {
-        let result = match op {
-            "+" => a + b,
-            "-" => a - b,
-            "*" => a * b,
-            "/" if b != 0.0 => a / b,
-            "/" => return format!("Error: Division by zero"),
-            _ => return format!("Error: Unknown operation '{}'", op),
-        };
-        
-        let output = format!("The result of {} {} {} is {}", a, op, b, result);
-        println!("📤 Function output: {}", output);
-        output
-    } else {
-        let error = "Invalid parameters for calculation".to_string();
-        println!("❌ Function error: {}", error);
-        error
+// Saving is best effort; a full disk shouldn't end the conversation
+fn save_session(store: &SessionStore, session: &mut Session) {
+    if let Err(e) = store.save(session) {
+        println!("⚠️ Could not save session: {:#}", e);
     }
 }
 
-fn handle_chat_response<'a>(
-    client: &'a Client,
-    api_key: &'a str,
-    chat_response: ChatResponse,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
-    Box::pin(async move {
-        for choice in chat_response.choices {
-            if let Some(message) = choice.message {
-                if let Some(captures) = FUNCTION_REGEX.captures(&message.content) {
-                    if let (Some(function_name), Some(params_str)) = (captures.get(1), captures.get(2)) {
-                        let function_name = function_name.as_str();
-                        let params_str = params_str.as_str();
-                        
-                        println!("\n🤖 Model requested function: {}", function_name);
-                        println!("📥 With parameters: {}", params_str);
-                        
-                        if let Ok(params) = serde_json::from_str(params_str) {
-                            if let Some(handler) = FUNCTION_REGISTRY.get(function_name) {
-                                let result = handler(params);
-                                println!("✅ Function executed successfully");
-                                
-                                let new_message = Message {
-                                    role: "user".to_string(),
-                                    content: result,
-                                };
-
-                                let new_request_payload = ChatRequest {
-                                    model: "llama-3.3-70b-versatile".to_string(),
-                                    messages: vec![new_message],
-                                    tools: vec![],
-                                    tool_choice: "auto".to_string(),
-                                };
-
-                                let response = client
-                                    .post("https://api.groq.com/openai/v1/chat/completions")
-                                    .header("Content-Type", "application/json")
-                                    .header("Authorization", format!("Bearer {}", api_key))
-                                    .json(&new_request_payload)
-                                    .send()
-                                    .await?;
-
-                                let new_chat_response: ChatResponse = response.json().await?;
-                                handle_chat_response(client, api_key, new_chat_response).await?;
-                            } else {
-                                println!("❌ Function '{}' not found in registry", function_name);
-                            }
-                        } else {
-                            println!("❌ Invalid parameter format: {}", params_str);
-                        }
-                    }
-                } else {
-                    println!("\n🤖 Chatbot: {}", message.content);
-                }
-            } else if let Some(text) = choice.text {
-                println!("\n🤖 Chatbot: {}", text);
-            }
-        }
-        Ok(())
-    })
+fn build_agent(config: Config) -> Result<Agent> {
+    Agent::builder()
+        .config(config)
+        .tools(FUNCTION_REGISTRY.clone())
+        .subscribe(ConsolePrinter::new())
+        .build()
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    let api_key = env::var("GROQ_API_KEY").expect("GROQ_API_KEY not set");
-    println!("Loaded API key");
-
-    let client = Client::new();
+    let cli = Cli::parse();
+    let store = SessionStore::new(&cli.sessions_dir);
+    if cli.list_sessions {
+        println!("{}", store.format_list()?);
+        return Ok(());
+    }
 
-    let tools = vec![Tool {
-        tool_type: "function".to_string(),
-        function: ToolFunction {
-            name: "calculate".to_string(),
-            description: "Calculator tool that performs basic arithmetic operations".to_string(),
-            parameters: ToolFunctionParameters {
-                param_type: "object".to_string(),
-                properties: serde_json::json!({
-                    "a": {
-                        "type": "number",
-                        "description": "First number",
-                    },
-                    "b": {
-                        "type": "number",
-                        "description": "Second number",
-                    },
-                    "operation": {
-                        "type": "string",
-                        "description": "Operation to perform (+, -, *, /)",
-                        "enum": ["+", "-", "*", "/"]
-                    }
-                }),
-                required: vec!["a".to_string(), "b".to_string(), "operation".to_string()],
-            },
-        },
-    }];
+    let resumed = match &cli.resume {
+        Some(id) => Some(store.load(id)?),
+        None if cli.continue_session => Some(
+            store
+                .latest()?
+                .ok_or_else(|| anyhow::anyhow!("no saved sessions in {}", cli.sessions_dir.display()))?,
+        ),
+        None => None,
+    };
+    let config = match &resumed {
+        Some(session) => Config::resume(session.config.clone(), &cli)?,
+        None => Config::load(&cli)?,
+    };
+    let _telemetry = groq_agent::telemetry::init(&config.telemetry)?;
+    let mut agent = build_agent(config.clone())?;
+    let mut session = match resumed {
+        Some(mut session) => {
+            session.config = config;
+            println!(
+                "📂 Resumed session {} ({} messages)",
+                session.id,
+                session.conversation.len()
+            );
+            session
+        }
+        None => Session::new(&config, agent.new_conversation()),
+    };
+    println!("Using model {} via {}", session.config.model, agent.provider().name());
+    println!("Type /help for commands");
 
     loop {
         print!("Enter your message: ");
         io::stdout().flush()?;
         let mut user_input = String::new();
-        io::stdin().read_line(&mut user_input)?;
+        let read = io::stdin().read_line(&mut user_input)?;
         let user_input = user_input.trim();
 
-        if user_input.eq_ignore_ascii_case("exit") {
+        if read == 0 || user_input.eq_ignore_ascii_case("exit") {
             println!("Exiting...");
             break;
         }
 
-        let request_payload = ChatRequest {
-            model: "llama-3.3-70b-versatile".to_string(),
-            messages: vec![
-                Message {
-                    role: "system".to_string(),
-                    content: "You are a helpful assistant with access to a calculator. When users want to perform arithmetic operations, use the calculate function by responding with: <function=calculate{\"a\": number1, \"b\": number2, \"operation\": \"op\"}> where op can be +, -, *, or /. After receiving results, provide a friendly response.".to_string(),
-                },
-                Message {
-                    role: "user".to_string(),
-                    content: user_input.to_string(),
-                },
-            ],
-            tools: tools.clone(),
-            tool_choice: "auto".to_string(),
+        let provider_settings = (session.config.provider, session.config.base_url.clone());
+        let mut ctx = CommandContext {
+            session: &mut session,
+            store: &store,
+            tools: agent.tools(),
+            commands: &COMMAND_REGISTRY,
+        };
+        let user_input = match COMMAND_REGISTRY.dispatch(user_input, &mut ctx) {
+            None => user_input.to_string(),
+            Some(Ok(CommandOutcome::Continue)) => {
+                // `/load` may switch to a session saved with another provider
+                if (session.config.provider, session.config.base_url.clone()) != provider_settings {
+                    match build_agent(session.config.clone()) {
+                        Ok(rebuilt) => {
+                            agent = rebuilt;
+                            println!("Using model {} via {}", session.config.model, agent.provider().name());
+                        }
+                        Err(e) => {
+                            // Carry on with the loaded conversation on the current provider
+                            println!("❌ {:#}", e);
+                            (session.config.provider, session.config.base_url) = provider_settings;
+                            println!("Staying on {}", agent.provider().name());
+                        }
+                    }
+                }
+                continue;
+            }
+            Some(Ok(CommandOutcome::Send(input))) => input,
+            Some(Ok(CommandOutcome::Exit)) => {
+                println!("Exiting...");
+                break;
+            }
+            Some(Err(e)) => {
+                println!("❌ {:#}", e);
+                continue;
+            }
         };
 
-        let response = client
-            .post("https://api.groq.com/openai/v1/chat/completions")
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .json(&request_payload)
-            .send()
-            .await?;
+        // Commands such as `/model` edit the session's copy of the settings
+        agent.set_config(session.config.clone());
+        let turn_start = session.conversation.len();
+        session.conversation.push(Message::new("user", user_input.clone()));
+
+        match agent.chat(&mut session.conversation).await {
+            Ok(turn_usage) => {
+                session.failed_input = None;
+                session.usage.merge(&turn_usage);
+                println!("📊 Turn: {}", turn_usage);
+                println!("📊 Session: {}", session.usage);
+                save_session(&store, &mut session);
+            }
+            Err(e) => {
+                report_error(&e);
+                // Drop the failed turn so the history stays well-formed,
+                // but keep its input for `/retry`
+                session.conversation.truncate(turn_start);
+                session.failed_input = Some(user_input);
+            }
+        }
+    }
 
-        let chat_response: ChatResponse = response.json().await?;
-        handle_chat_response(&client, &api_key, chat_response).await?;
+    if session.conversation.has_user_messages() {
+        save_session(&store, &mut session);
+        println!("💾 Session {} saved. Resume it with --resume {}", session.id, session.id);
     }
 
     Ok(())
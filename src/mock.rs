@@ -0,0 +1,370 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::{Message, ToolCall};
+
+const CHAT_COMPLETIONS_PATH: &str = "/openai/v1/chat/completions";
+
+/// A canned reply for the mock server to send.
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: MockBody,
+    // Sent before `[DONE]` only if the request set
+    // `stream_options.include_usage`, as OpenAI does
+    stream_usage: Option<Value>,
+}
+
+#[derive(Debug, Clone)]
+enum MockBody {
+    Full(String),
+    // Each event is written separately, the way a real server streams them
+    Events(Vec<String>),
+}
+
+impl MockResponse {
+    /// A completed chat reply carrying `message`.
+    pub fn message(message: Value) -> Self {
+        Self::json(
+            200,
+            json!({
+                "object": "chat.completion",
+                "choices": [{ "index": 0, "message": message, "finish_reason": "stop" }],
+            }),
+        )
+    }
+
+    /// A plain assistant answer.
+    pub fn text(content: impl Into<String>) -> Self {
+        Self::message(json!({ "role": "assistant", "content": content.into() }))
+    }
+
+    /// An answer calling a tool through an inline `<function=name{...}>` tag
+    /// instead of native tool calls, as some models do.
+    pub fn inline_call(name: &str, arguments: Value) -> Self {
+        Self::text(format!("<function={}{}></function>", name, arguments))
+    }
+
+    /// An answer with a single native tool call.
+    pub fn tool_call(id: &str, name: &str, arguments: Value) -> Self {
+        Self::tool_calls(vec![ToolCall {
+            id: id.to_string(),
+            call_type: crate::default_tool_type(),
+            function: crate::FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }])
+    }
+
+    /// An answer with native tool calls and no content.
+    pub fn tool_calls(calls: Vec<ToolCall>) -> Self {
+        Self::message(json!({ "role": "assistant", "content": null, "tool_calls": calls }))
+    }
+
+    /// A streamed reply made of the given `chat.completion.chunk` payloads,
+    /// followed by `[DONE]`.
+    pub fn stream(chunks: impl IntoIterator<Item = Value>) -> Self {
+        let mut events: Vec<String> = chunks
+            .into_iter()
+            .map(|chunk| format!("data: {}\n\n", chunk))
+            .collect();
+        events.push("data: [DONE]\n\n".to_string());
+        MockResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/event-stream".to_string())],
+            body: MockBody::Events(events),
+            stream_usage: None,
+        }
+    }
+
+    /// A streamed answer sent one token per chunk.
+    pub fn streamed_text<'a>(tokens: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut chunks = vec![json!({
+            "object": "chat.completion.chunk",
+            "choices": [{ "index": 0, "delta": { "role": "assistant", "content": "" } }],
+        })];
+        chunks.extend(tokens.into_iter().map(|token| {
+            json!({
+                "object": "chat.completion.chunk",
+                "choices": [{ "index": 0, "delta": { "content": token } }],
+            })
+        }));
+        chunks.push(json!({
+            "object": "chat.completion.chunk",
+            "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }],
+        }));
+        Self::stream(chunks)
+    }
+
+    /// A 429 asking the client to wait `retry_after_secs` before retrying.
+    pub fn rate_limited(retry_after_secs: u64) -> Self {
+        Self::error(429, "rate_limit_exceeded", "Rate limit reached, please try again later")
+            .with_header("retry-after", &retry_after_secs.to_string())
+    }
+
+    /// An OpenAI-style error envelope with the given status.
+    pub fn error(status: u16, code: &str, message: &str) -> Self {
+        let error_type = match status {
+            401 | 403 => "authentication_error",
+            429 => "rate_limit_error",
+            _ => "invalid_request_error",
+        };
+        Self::json(
+            status,
+            json!({ "error": { "message": message, "type": error_type, "code": code } }),
+        )
+    }
+
+    /// A 200 whose body is sent verbatim, e.g. truncated or invalid JSON.
+    pub fn raw(body: impl Into<String>) -> Self {
+        MockResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: MockBody::Full(body.into()),
+            stream_usage: None,
+        }
+    }
+
+    /// Adds a `usage` block to a completed reply, or a final usage chunk to
+    /// a stream whose request asked for one.
+    pub fn with_usage(mut self, prompt_tokens: u64, completion_tokens: u64) -> Self {
+        let usage = json!({
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        });
+        match &mut self.body {
+            MockBody::Full(body) => {
+                if let Ok(Value::Object(mut object)) = serde_json::from_str::<Value>(body) {
+                    object.insert("usage".to_string(), usage);
+                    *body = Value::Object(object).to_string();
+                }
+            }
+            MockBody::Events(_) => {
+                self.stream_usage =
+                    Some(json!({ "object": "chat.completion.chunk", "choices": [], "usage": usage }));
+            }
+        }
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_lowercase(), value.to_string()));
+        self
+    }
+
+    fn json(status: u16, body: Value) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: MockBody::Full(body.to_string()),
+            stream_usage: None,
+        }
+    }
+}
+
+/// A request the mock server received.
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase.
+    pub headers: BTreeMap<String, String>,
+    /// The JSON body, or `Null` if it wasn't JSON.
+    pub body: Value,
+}
+
+impl ReceivedRequest {
+    /// The `messages` the client sent, in order.
+    pub fn messages(&self) -> Vec<Message> {
+        serde_json::from_value(self.body["messages"].clone()).unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: VecDeque<MockResponse>,
+    requests: Vec<ReceivedRequest>,
+}
+
+/// A local stand-in for the OpenAI-compatible `/chat/completions` endpoint,
+/// for tests. Queue replies with [`MockServer::enqueue`], point the agent's
+/// `base_url` at [`MockServer::base_url`], then inspect what it sent with
+/// [`MockServer::requests`]. Each request to the endpoint takes the next
+/// queued reply; once the queue is empty it gets a 400. Like OpenAI, it
+/// also answers 400 to a `tool_choice` sent without `tools`.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Listens on a free port on localhost until dropped.
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("failed to bind mock server")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let shared = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, shared.clone()));
+            }
+        });
+        Ok(MockServer { addr, state, task })
+    }
+
+    /// The base URL to configure, mirroring Groq's `/openai/v1` prefix.
+    pub fn base_url(&self) -> String {
+        format!("http://{}/openai/v1", self.addr)
+    }
+
+    pub fn enqueue(&self, response: MockResponse) -> &Self {
+        self.lock().responses.push_back(response);
+        self
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Replies queued but not served yet.
+    pub fn pending(&self) -> usize {
+        self.lock().responses.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// Handles one request per connection and closes it, so clients never reuse
+// a connection the server is about to drop
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<MockState>>) {
+    let Ok(Some(request)) = read_request(&mut stream).await else {
+        return;
+    };
+    let (response, include_usage) = {
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        let has_tools = request.body["tools"].as_array().is_some_and(|tools| !tools.is_empty());
+        let response = if request.method != "POST" || request.path != CHAT_COMPLETIONS_PATH {
+            MockResponse::error(404, "unknown_url", &format!("Unknown request URL: {}", request.path))
+        } else if request.body.get("tool_choice").is_some() && !has_tools {
+            // Rejected by OpenAI, without using up a queued reply
+            MockResponse::error(
+                400,
+                "invalid_request",
+                "'tool_choice' is only allowed when 'tools' are specified",
+            )
+        } else {
+            state.responses.pop_front().unwrap_or_else(|| {
+                MockResponse::error(400, "mock_exhausted", "the mock server has no response queued")
+            })
+        };
+        let include_usage = request.body["stream_options"]["include_usage"] == true;
+        state.requests.push(request);
+        (response, include_usage)
+    };
+    let _ = write_response(&mut stream, &response, include_usage).await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<ReceivedRequest>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        if let Some(end) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: BTreeMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = buffer.split_off(header_end + 4);
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Ok(Some(ReceivedRequest {
+        method,
+        path,
+        headers,
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+    }))
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    response: &MockResponse,
+    include_usage: bool,
+) -> std::io::Result<()> {
+    let reason = http::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("");
+    let mut head = format!("HTTP/1.1 {} {}\r\nconnection: close\r\n", response.status, reason);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+
+    match &response.body {
+        MockBody::Full(body) => {
+            head.push_str(&format!("content-length: {}\r\n\r\n", body.len()));
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(body.as_bytes()).await?;
+        }
+        // Without a length the body runs until the connection closes
+        MockBody::Events(events) => {
+            head.push_str("\r\n");
+            stream.write_all(head.as_bytes()).await?;
+            let (done, chunks) = events.split_last().expect("streams end with [DONE]");
+            let usage = response
+                .stream_usage
+                .as_ref()
+                .filter(|_| include_usage)
+                .map(|chunk| format!("data: {}\n\n", chunk));
+            for event in chunks.iter().chain(usage.as_ref()).chain([done]) {
+                stream.write_all(event.as_bytes()).await?;
+                stream.flush().await?;
+            }
+        }
+    }
+    stream.shutdown().await
+}
@@ -0,0 +1,148 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::env;
+use std::sync::Arc;
+
+use crate::cassette::{RecordingTransport, ReplayTransport};
+use crate::config::{Config, ProviderKind};
+use crate::error::ApiError;
+use crate::events::EventBus;
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
+use crate::{ChatRequest, ChatResponse};
+
+pub mod anthropic;
+pub mod openai;
+
+pub use anthropic::Anthropic;
+pub use openai::OpenAiCompatible;
+
+/// Receives each content fragment of a streamed reply.
+pub type OnToken<'a> = dyn FnMut(&str) + Send + 'a;
+
+/// A chat backend. Implementations translate `ChatRequest`/`ChatResponse` to
+/// and from their wire format, so the tool loop never sees provider details.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Receives the agent's event bus, so retries and rate-limit waits can be
+    /// reported. Providers with nothing to report can ignore it.
+    fn set_events(&mut self, _events: EventBus) {}
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, ApiError>;
+
+    /// Streams the reply, calling `on_token` with each content fragment.
+    /// Providers without streaming support send the whole reply as one fragment.
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        on_token: &mut OnToken<'_>,
+    ) -> Result<ChatResponse, ApiError> {
+        let response = self.chat(request).await?;
+        for choice in &response.choices {
+            if let Some(message) = choice.message.as_ref().filter(|m| !m.content.is_empty()) {
+                on_token(&message.content);
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// The HTTP plumbing shared by the providers: every attempt waits for rate
+/// limit budget, goes out through the transport, feeds the limiter the
+/// response's rate-limit headers, and is retried per `retry`.
+pub(crate) struct HttpSender {
+    pub(crate) transport: Arc<dyn Transport>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+    pub(crate) events: EventBus,
+}
+
+impl Default for HttpSender {
+    fn default() -> Self {
+        HttpSender {
+            transport: Arc::new(ReqwestTransport::default()),
+            retry: RetryPolicy::default(),
+            rate_limiter: Arc::default(),
+            events: EventBus::default(),
+        }
+    }
+}
+
+impl HttpSender {
+    /// Sends `request`, estimated at `tokens` for rate limiting, and returns
+    /// the first successful response.
+    pub(crate) async fn send(&self, request: &HttpRequest, tokens: u64) -> Result<reqwest::Response, ApiError> {
+        self.retry
+            .run(&self.events, || async {
+                self.rate_limiter.acquire(tokens, &self.events).await;
+                let response = self.transport.send(request).await?;
+                self.rate_limiter.observe(response.headers());
+                ApiError::check_response(response).await
+            })
+            .await
+    }
+}
+
+/// Builds the provider selected in `config`, reading its API key from the
+/// environment. Replaying a cassette needs no key.
+pub fn from_config(config: &Config) -> Result<Box<dyn LlmProvider>> {
+    let rate_limiter = Arc::new(RateLimiter::new(&config.rate_limit));
+    let transport = transport_from_config(config)?;
+    let replaying = config.cassette.replay.is_some();
+    let api_key = |var: &str| match env::var(var) {
+        Err(_) if replaying => Ok(String::new()),
+        result => result.with_context(|| format!("{} not set", var)),
+    };
+    let provider: Box<dyn LlmProvider> = match config.provider {
+        ProviderKind::Groq => {
+            let mut provider = OpenAiCompatible::groq(api_key("GROQ_API_KEY")?)
+                .with_retry(config.retry.clone())
+                .with_rate_limiter(rate_limiter)
+                .with_transport(transport);
+            if let Some(base_url) = &config.base_url {
+                provider = provider.with_base_url(base_url);
+            }
+            Box::new(provider)
+        }
+        ProviderKind::OpenAi => {
+            let base_url = config
+                .base_url
+                .as_deref()
+                .unwrap_or(openai::OPENAI_BASE_URL);
+            // Local servers such as llama.cpp or Ollama usually need no key
+            Box::new(
+                OpenAiCompatible::new(base_url, env::var("OPENAI_API_KEY").ok())
+                    .with_retry(config.retry.clone())
+                    .with_rate_limiter(rate_limiter)
+                    .with_transport(transport),
+            )
+        }
+        ProviderKind::Anthropic => {
+            let mut provider = Anthropic::new(api_key("ANTHROPIC_API_KEY")?)
+                .with_retry(config.retry.clone())
+                .with_rate_limiter(rate_limiter)
+                .with_transport(transport);
+            if let Some(base_url) = &config.base_url {
+                provider = provider.with_base_url(base_url);
+            }
+            Box::new(provider)
+        }
+    };
+    Ok(provider)
+}
+
+fn transport_from_config(config: &Config) -> Result<Arc<dyn Transport>> {
+    let transport: Arc<dyn Transport> = match (&config.cassette.record, &config.cassette.replay) {
+        (Some(_), Some(_)) => bail!("a cassette can't be recorded and replayed at the same time"),
+        (Some(path), None) => Arc::new(RecordingTransport::new(
+            Arc::new(ReqwestTransport::default()),
+            path,
+        )),
+        (None, Some(path)) => Arc::new(ReplayTransport::load(path)?),
+        (None, None) => Arc::new(ReqwestTransport::default()),
+    };
+    Ok(transport)
+}
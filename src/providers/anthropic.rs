@@ -0,0 +1,236 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::{HttpSender, LlmProvider};
+use crate::error::ApiError;
+use crate::events::EventBus;
+use crate::rate_limit::{estimate_request_tokens, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::transport::{HttpRequest, Transport};
+use crate::usage::Usage;
+use crate::{ChatRequest, ChatResponse, Choice, FunctionCall, Message, ToolCall};
+
+pub const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// The Messages API requires `max_tokens`; used when the config leaves it unset
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Anthropic Messages API. System messages are lifted into the top-level
+/// `system` field, tool calls become `tool_use` blocks, and tool results are
+/// sent back as `tool_result` blocks in a user turn.
+pub struct Anthropic {
+    http: HttpSender,
+    base_url: String,
+    api_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessagesResponse {
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    usage: Option<MessagesUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessagesUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text { text: String },
+    ToolUse { id: String, name: String, input: Value },
+    #[serde(other)]
+    Other,
+}
+
+impl Anthropic {
+    pub fn new(api_key: String) -> Self {
+        Anthropic {
+            http: HttpSender::default(),
+            base_url: ANTHROPIC_BASE_URL.to_string(),
+            api_key,
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.http.retry = retry;
+        self
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. to
+    /// record or replay a cassette.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.http.transport = transport;
+        self
+    }
+
+    /// Paces requests through `rate_limiter`, which may be shared with other
+    /// providers using the same API key.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.http.rate_limiter = rate_limiter;
+        self
+    }
+}
+
+fn to_wire_request(request: &ChatRequest) -> Value {
+    let system: Vec<&str> = request
+        .messages
+        .iter()
+        .filter(|message| message.role == "system")
+        .map(|message| message.content.as_str())
+        .collect();
+
+    let mut messages: Vec<Value> = Vec::new();
+    for message in request.messages.iter().filter(|m| m.role != "system") {
+        let (role, blocks) = match message.role.as_str() {
+            "assistant" => {
+                let mut blocks = Vec::new();
+                if !message.content.is_empty() {
+                    blocks.push(json!({ "type": "text", "text": message.content }));
+                }
+                for call in message.tool_calls.iter().flatten() {
+                    let input: Value = serde_json::from_str(&call.function.arguments)
+                        .unwrap_or_else(|_| json!({}));
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": input,
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            "tool" => (
+                "user",
+                vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id,
+                    "content": message.content,
+                })],
+            ),
+            _ => ("user", vec![json!({ "type": "text", "text": message.content })]),
+        };
+
+        // Roles must alternate, so consecutive same-role turns (such as the
+        // results of parallel tool calls) are merged into one message.
+        match messages.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(content) = last["content"].as_array_mut() {
+                    content.extend(blocks);
+                }
+            }
+            _ => messages.push(json!({ "role": role, "content": blocks })),
+        }
+    }
+
+    let mut body = json!({
+        "model": request.model,
+        "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        "messages": messages,
+    });
+    if !system.is_empty() {
+        body["system"] = json!(system.join("\n\n"));
+    }
+    if !request.tools.is_empty() {
+        body["tools"] = request
+            .tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.function.name,
+                    "description": tool.function.description,
+                    "input_schema": tool.function.parameters,
+                })
+            })
+            .collect();
+        body["tool_choice"] = match request.tool_choice.as_str() {
+            Some("auto") => json!({ "type": "auto" }),
+            Some("none") => json!({ "type": "none" }),
+            Some("required") => json!({ "type": "any" }),
+            _ => json!({
+                "type": "tool",
+                "name": request.tool_choice["function"]["name"],
+            }),
+        };
+    }
+    if let Some(temperature) = request.temperature {
+        body["temperature"] = json!(temperature);
+    }
+    if let Some(top_p) = request.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if let Some(stop) = &request.stop {
+        body["stop_sequences"] = json!(stop);
+    }
+    body
+}
+
+fn from_wire_response(response: MessagesResponse) -> ChatResponse {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in response.content {
+        match block {
+            ContentBlock::Text { text: fragment } => text.push_str(&fragment),
+            ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                id,
+                call_type: crate::default_tool_type(),
+                function: FunctionCall {
+                    name,
+                    arguments: input.to_string(),
+                },
+            }),
+            ContentBlock::Other => {}
+        }
+    }
+
+    let mut message = Message::new("assistant", text);
+    message.tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+    ChatResponse {
+        choices: vec![Choice {
+            message: Some(message),
+            text: None,
+        }],
+        usage: response.usage.map(|usage| Usage {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.input_tokens + usage.output_tokens,
+            ..Usage::default()
+        }),
+        x_groq: None,
+    }
+}
+
+#[async_trait]
+impl LlmProvider for Anthropic {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn set_events(&mut self, events: EventBus) {
+        self.http.events = events;
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, ApiError> {
+        let http_request =
+            HttpRequest::post(format!("{}/messages", self.base_url), &to_wire_request(request))?
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION);
+        let response = self.http.send(&http_request, estimate_request_tokens(request)).await?;
+
+        let response: MessagesResponse = response.json().await?;
+        Ok(from_wire_response(response))
+    }
+}
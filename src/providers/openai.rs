@@ -0,0 +1,105 @@
+use async_trait::async_trait;
+use reqwest::Response;
+use std::sync::Arc;
+
+use super::{HttpSender, LlmProvider, OnToken};
+use crate::error::ApiError;
+use crate::events::EventBus;
+use crate::rate_limit::{estimate_request_tokens, RateLimiter};
+use crate::retry::RetryPolicy;
+use crate::stream::read_chat_stream;
+use crate::transport::{HttpRequest, Transport};
+use crate::{ChatRequest, ChatResponse, StreamOptions};
+
+pub const GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Any server implementing the OpenAI `/chat/completions` API: Groq, OpenAI,
+/// or a local llama.cpp, vLLM or Ollama instance.
+pub struct OpenAiCompatible {
+    name: String,
+    http: HttpSender,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatible {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        OpenAiCompatible {
+            name: "openai".to_string(),
+            http: HttpSender::default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+
+    pub fn groq(api_key: String) -> Self {
+        OpenAiCompatible {
+            name: "groq".to_string(),
+            ..Self::new(GROQ_BASE_URL, Some(api_key))
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.http.retry = retry;
+        self
+    }
+
+    /// Sends requests through `transport` instead of the network, e.g. to
+    /// record or replay a cassette.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.http.transport = transport;
+        self
+    }
+
+    /// Paces requests through `rate_limiter`, which may be shared with other
+    /// providers using the same API key.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.http.rate_limiter = rate_limiter;
+        self
+    }
+
+    async fn post(&self, request: &ChatRequest, stream: bool) -> Result<Response, ApiError> {
+        let request = ChatRequest {
+            stream: stream.then_some(true),
+            stream_options: stream.then_some(StreamOptions { include_usage: true }),
+            ..request.clone()
+        };
+        let mut http_request =
+            HttpRequest::post(format!("{}/chat/completions", self.base_url), &request)?;
+        if let Some(api_key) = &self.api_key {
+            http_request = http_request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        self.http.send(&http_request, estimate_request_tokens(&request)).await
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiCompatible {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn set_events(&mut self, events: EventBus) {
+        self.http.events = events;
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, ApiError> {
+        let response = self.post(request, false).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        on_token: &mut OnToken<'_>,
+    ) -> Result<ChatResponse, ApiError> {
+        let response = self.post(request, true).await?;
+        read_chat_stream(response, on_token).await
+    }
+}
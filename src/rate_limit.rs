@@ -0,0 +1,220 @@
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::events::{AgentEvent, EventBus};
+use crate::retry::parse_reset_duration;
+use crate::ChatRequest;
+
+/// Optional fixed budgets, for keys whose limits are known up front or to
+/// leave headroom for other clients sharing the key.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Paces requests so they stay under the provider's limits instead of
+/// running into 429s. Budgets come from the configured RPM/TPM and from the
+/// `x-ratelimit-remaining-*` headers of earlier responses, which reflect
+/// usage by every client on the same key. Share one limiter (behind an
+/// `Arc`) between everything that uses the same key in this process.
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+struct State {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    server: ServerBudget,
+}
+
+// Refills continuously at `per_minute / 60` units per second up to one
+// minute's worth.
+struct Bucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32) -> Self {
+        let capacity = f64::from(per_minute.max(1));
+        Bucket {
+            capacity,
+            available: capacity,
+            per_second: capacity / 60.0,
+            updated: Instant::now(),
+        }
+    }
+
+    fn wait_for(&mut self, amount: f64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+
+        // A request larger than the whole bucket only waits for a full bucket
+        let amount = amount.min(self.capacity);
+        if self.available >= amount {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount - self.available) / self.per_second)
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.available -= amount.min(self.capacity);
+    }
+}
+
+/// Remaining budgets as last reported by the server.
+#[derive(Default)]
+struct ServerBudget {
+    remaining_requests: Option<u64>,
+    requests_reset_at: Option<Instant>,
+    remaining_tokens: Option<u64>,
+    tokens_reset_at: Option<Instant>,
+}
+
+impl ServerBudget {
+    fn wait_for(&mut self, tokens: u64, now: Instant) -> Duration {
+        // Once a window has reset the old numbers no longer apply
+        if self.requests_reset_at.is_some_and(|reset| reset <= now) {
+            self.remaining_requests = None;
+            self.requests_reset_at = None;
+        }
+        if self.tokens_reset_at.is_some_and(|reset| reset <= now) {
+            self.remaining_tokens = None;
+            self.tokens_reset_at = None;
+        }
+
+        let until = |reset: Option<Instant>| {
+            reset.map_or(Duration::ZERO, |reset| reset.saturating_duration_since(now))
+        };
+        let mut wait = Duration::ZERO;
+        if self.remaining_requests == Some(0) {
+            wait = wait.max(until(self.requests_reset_at));
+        }
+        if self.remaining_tokens.is_some_and(|remaining| remaining < tokens) {
+            wait = wait.max(until(self.tokens_reset_at));
+        }
+        wait
+    }
+
+    fn take(&mut self, tokens: u64) {
+        if let Some(remaining) = &mut self.remaining_requests {
+            *remaining = remaining.saturating_sub(1);
+        }
+        if let Some(remaining) = &mut self.remaining_tokens {
+            *remaining = remaining.saturating_sub(tokens);
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        RateLimiter {
+            state: Mutex::new(State {
+                requests: config.requests_per_minute.map(Bucket::new),
+                tokens: config.tokens_per_minute.map(Bucket::new),
+                server: ServerBudget::default(),
+            }),
+        }
+    }
+
+    /// Waits until a request of about `tokens` tokens fits in every budget,
+    /// then reserves it. Waits are announced on `events`.
+    pub async fn acquire(&self, tokens: u64, events: &EventBus) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().expect("rate limiter lock poisoned");
+                let now = Instant::now();
+                let mut wait = state.server.wait_for(tokens, now);
+                if let Some(bucket) = &mut state.requests {
+                    wait = wait.max(bucket.wait_for(1.0, now));
+                }
+                if let Some(bucket) = &mut state.tokens {
+                    wait = wait.max(bucket.wait_for(tokens as f64, now));
+                }
+
+                if wait.is_zero() {
+                    state.server.take(tokens);
+                    if let Some(bucket) = &mut state.requests {
+                        bucket.take(1.0);
+                    }
+                    if let Some(bucket) = &mut state.tokens {
+                        bucket.take(tokens as f64);
+                    }
+                }
+                wait
+            };
+
+            if wait.is_zero() {
+                return;
+            }
+            tracing::debug!(wait_ms = wait.as_millis() as u64, "pacing request");
+            events.emit(AgentEvent::Pacing { wait });
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Records the budgets reported in a response's `x-ratelimit-*` headers.
+    pub fn observe(&self, headers: &HeaderMap) {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let remaining = |name: &str| header(name).and_then(|value| value.trim().parse::<u64>().ok());
+        let reset_at = |name: &str| {
+            header(name)
+                .and_then(parse_reset_duration)
+                .and_then(|delay| Instant::now().checked_add(delay))
+        };
+
+        let mut state = self.state.lock().expect("rate limiter lock poisoned");
+        if let Some(requests) = remaining("x-ratelimit-remaining-requests") {
+            state.server.remaining_requests = Some(requests);
+            state.server.requests_reset_at = reset_at("x-ratelimit-reset-requests");
+        }
+        if let Some(tokens) = remaining("x-ratelimit-remaining-tokens") {
+            state.server.remaining_tokens = Some(tokens);
+            state.server.tokens_reset_at = reset_at("x-ratelimit-reset-tokens");
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new(&RateLimitConfig::default())
+    }
+}
+
+/// Rough token cost of a request for budgeting: about four bytes of JSON per
+/// prompt token, plus the completion tokens it may generate.
+pub fn estimate_request_tokens(request: &ChatRequest) -> u64 {
+    let prompt = serde_json::to_string(&request.messages).map_or(0, |json| json.len()) / 4;
+    prompt as u64 + u64::from(request.max_tokens.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn malformed_reset_headers_are_ignored() {
+        let limiter = RateLimiter::default();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", HeaderValue::from_static("0"));
+        headers.insert("x-ratelimit-reset-requests", HeaderValue::from_static("inf"));
+        headers.insert("x-ratelimit-remaining-tokens", HeaderValue::from_static("0"));
+        // Parses, but lies beyond any representable `Instant`
+        headers.insert("x-ratelimit-reset-tokens", HeaderValue::from_static("18446744073709551615s"));
+        limiter.observe(&headers);
+
+        let mut state = limiter.state.lock().unwrap();
+        assert_eq!(state.server.requests_reset_at, None);
+        assert_eq!(state.server.tokens_reset_at, None);
+        assert_eq!(state.server.wait_for(1, Instant::now()), Duration::ZERO);
+    }
+}
@@ -0,0 +1,320 @@
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+use crate::events::{AgentEvent, EventBus};
+
+/// How failed chat requests are retried. Rate limits (429), server errors
+/// (5xx) and connection failures are retried with exponential backoff and
+/// jitter; a server-provided wait from the rate-limit headers takes
+/// precedence over the computed backoff, up to `max_delay_ms`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first; 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Overall budget across all attempts and waits.
+    pub deadline_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            deadline_secs: 120,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Runs `attempt` until it succeeds, fails with a non-retryable error, or
+    /// the attempt or deadline budget runs out. Each retry is announced on
+    /// `events`.
+    pub async fn run<T, F, Fut>(&self, events: &EventBus, mut attempt: F) -> Result<T, ApiError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        // A deadline too far out to represent means there is none
+        let deadline = Instant::now().checked_add(Duration::from_secs(self.deadline_secs));
+        let max_attempts = self.max_attempts.max(1);
+
+        for attempt_number in 1.. {
+            let error = match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if attempt_number >= max_attempts || !error.is_retryable() {
+                return Err(error);
+            }
+
+            let delay = error
+                .retry_after()
+                .map(|wait| wait.min(Duration::from_millis(self.max_delay_ms)))
+                .unwrap_or_else(|| self.backoff(attempt_number));
+            if let Some(deadline) = deadline {
+                let retry_at = Instant::now().checked_add(delay);
+                if retry_at.is_none_or(|retry_at| retry_at > deadline) {
+                    return Err(error);
+                }
+            }
+
+            tracing::warn!(
+                error = %error,
+                kind = error.kind(),
+                delay_ms = delay.as_millis() as u64,
+                attempt = attempt_number + 1,
+                "retrying request"
+            );
+            events.emit(AgentEvent::Retrying {
+                error: error.to_string(),
+                delay,
+                attempt: attempt_number + 1,
+                max_attempts,
+            });
+            tokio::time::sleep(delay).await;
+        }
+        unreachable!("the retry loop only exits by returning")
+    }
+
+    // "Equal jitter": half the exponential delay is fixed, half is random, so
+    // clients sharing a key don't retry in lockstep.
+    fn backoff(&self, attempt_number: u32) -> Duration {
+        let exponential = self
+            .base_delay_ms
+            .saturating_mul(1u64 << (attempt_number - 1).min(20))
+            .min(self.max_delay_ms);
+        let half = exponential / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half);
+        Duration::from_millis(half + jitter)
+    }
+}
+
+/// How long the server asked us to wait, from `retry-after` or, failing
+/// that, the reset time of whichever `x-ratelimit-*` budget is exhausted.
+pub fn retry_after_from_headers(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(delay) = header("retry-after")
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .and_then(seconds_to_duration)
+    {
+        return Some(delay);
+    }
+
+    ["requests", "tokens"]
+        .iter()
+        .filter(|budget| {
+            header(&format!("x-ratelimit-remaining-{}", budget))
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                == Some(0)
+        })
+        .filter_map(|budget| header(&format!("x-ratelimit-reset-{}", budget)))
+        .filter_map(parse_reset_duration)
+        .max()
+}
+
+/// Parses rate-limit reset durations as Groq and OpenAI send them, such as
+/// `1ms`, `7.66s`, `2m59.56s` or `1h2m`.
+pub fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let seconds_per_unit = match c {
+            'h' => 3600.0,
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                0.001
+            }
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return None,
+        };
+        total += number.parse::<f64>().ok()? * seconds_per_unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        // A bare number is seconds
+        total += number.parse::<f64>().ok()?;
+    }
+    seconds_to_duration(total)
+}
+
+// Header values are server-controlled: `inf`, `NaN` or a huge number must
+// not panic in `Duration::from_secs_f64`. A negative wait means none.
+fn seconds_to_duration(seconds: f64) -> Option<Duration> {
+    if !seconds.is_finite() {
+        return None;
+    }
+    Duration::try_from_secs_f64(seconds.max(0.0)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_reset_durations() {
+        assert_eq!(parse_reset_duration("1ms"), Some(Duration::from_millis(1)));
+        assert_eq!(parse_reset_duration("7.5s"), Some(Duration::from_millis(7500)));
+        assert_eq!(parse_reset_duration("2m30s"), Some(Duration::from_secs(150)));
+        assert_eq!(parse_reset_duration("1h2m"), Some(Duration::from_secs(3720)));
+        assert_eq!(parse_reset_duration(" 3 "), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn rejects_malformed_reset_durations() {
+        for value in ["", "abc", "1x", "-1s", "1.2.3s", "s", "inf", "NaN", "99999999999999999999s", "1e400"] {
+            assert_eq!(parse_reset_duration(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn retry_after_takes_precedence() {
+        let both = headers(&[
+            ("retry-after", "2"),
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "10s"),
+        ]);
+        assert_eq!(retry_after_from_headers(&both), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn falls_back_to_the_longest_exhausted_reset() {
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "3s"),
+            ("x-ratelimit-remaining-tokens", "0"),
+            ("x-ratelimit-reset-tokens", "1m"),
+        ]);
+        assert_eq!(retry_after_from_headers(&exhausted), Some(Duration::from_secs(60)));
+
+        // Budgets that aren't exhausted don't count
+        let tokens_left = headers(&[
+            ("x-ratelimit-remaining-requests", "0"),
+            ("x-ratelimit-reset-requests", "3s"),
+            ("x-ratelimit-remaining-tokens", "100"),
+            ("x-ratelimit-reset-tokens", "1m"),
+        ]);
+        assert_eq!(retry_after_from_headers(&tokens_left), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn ignores_malformed_retry_headers() {
+        for value in ["inf", "NaN", "-inf", "1e400", "soon", ""] {
+            assert_eq!(retry_after_from_headers(&headers(&[("retry-after", value)])), None, "{:?}", value);
+        }
+        assert_eq!(retry_after_from_headers(&headers(&[("retry-after", "-5")])), Some(Duration::ZERO));
+
+        let malformed = headers(&[
+            ("retry-after", "inf"),
+            ("x-ratelimit-remaining-tokens", "0"),
+            ("x-ratelimit-reset-tokens", "99999999999999999999s"),
+        ]);
+        assert_eq!(retry_after_from_headers(&malformed), None);
+    }
+
+    #[tokio::test]
+    async fn server_waits_are_capped_at_max_delay() {
+        let policy = RetryPolicy {
+            max_delay_ms: 10,
+            ..RetryPolicy::default()
+        };
+        let mut events = EventBus::default();
+        let delays = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = delays.clone();
+        events.subscribe(move |event: &AgentEvent| {
+            if let AgentEvent::Retrying { delay, .. } = event {
+                sink.lock().unwrap().push(*delay);
+            }
+        });
+
+        let mut attempts = 0;
+        let result = policy
+            .run(&events, || {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt == 1 {
+                        Err(ApiError::RateLimited {
+                            message: "slow down".to_string(),
+                            retry_after: Some(Duration::from_secs(3600)),
+                        })
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(*delays.lock().unwrap(), [Duration::from_millis(10)]);
+    }
+
+    fn fails_once(attempts: &mut u32) -> impl Future<Output = Result<u32, ApiError>> {
+        *attempts += 1;
+        let attempt = *attempts;
+        async move {
+            if attempt == 1 {
+                Err(ApiError::RateLimited {
+                    message: "slow down".to_string(),
+                    retry_after: None,
+                })
+            } else {
+                Ok(attempt)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn huge_deadlines_mean_no_deadline() {
+        let policy = RetryPolicy {
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            deadline_secs: u64::MAX,
+            ..RetryPolicy::default()
+        };
+        let mut attempts = 0;
+        let result = policy.run(&EventBus::default(), || fails_once(&mut attempts)).await;
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn delays_past_any_representable_time_give_up() {
+        let policy = RetryPolicy {
+            base_delay_ms: u64::MAX,
+            max_delay_ms: u64::MAX,
+            ..RetryPolicy::default()
+        };
+        let mut attempts = 0;
+        let result = policy.run(&EventBus::default(), || fails_once(&mut attempts)).await;
+        assert!(matches!(result, Err(ApiError::RateLimited { .. })));
+        assert_eq!(attempts, 1);
+    }
+}
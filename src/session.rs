@@ -0,0 +1,234 @@
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::usage::UsageTotals;
+use crate::Conversation;
+
+/// Everything needed to pick a conversation back up: the history with its
+/// tool calls and results, the settings it ran with, and its usage so far.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    /// Unix time, in seconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub model: String,
+    pub config: Config,
+    pub conversation: Conversation,
+    pub usage: UsageTotals,
+    /// Input of the last turn if it failed and was dropped from the
+    /// conversation, so `/retry` can send it again. Not saved.
+    #[serde(skip)]
+    pub failed_input: Option<String>,
+}
+
+impl Session {
+    pub fn new(config: &Config, conversation: Conversation) -> Self {
+        let now = unix_now();
+        Session {
+            // Sorts by creation time; the suffix keeps concurrent REPLs apart
+            id: format!("{}-{:04x}", now, rand::thread_rng().gen::<u16>()),
+            created_at: now,
+            updated_at: now,
+            model: config.model.clone(),
+            config: config.clone(),
+            conversation,
+            usage: UsageTotals::default(),
+            failed_input: None,
+        }
+    }
+
+    // First user message, for listings
+    fn title(&self) -> String {
+        let first = self
+            .conversation
+            .messages()
+            .iter()
+            .find(|message| message.role == "user")
+            .map_or("", |message| message.content.as_str());
+        let mut title: String = first.chars().take(60).collect();
+        if first.chars().count() > 60 {
+            title.push('…');
+        }
+        title
+    }
+}
+
+/// Sessions stored as one `<id>.json` file each in a directory.
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SessionStore { dir: dir.into() }
+    }
+
+    pub fn save(&self, session: &mut Session) -> Result<PathBuf> {
+        session.updated_at = unix_now();
+        session.model = session.config.model.clone();
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let path = self.path(&session.id)?;
+        // Write then rename, so a crash mid-save never leaves a truncated file
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(session)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(&self, id: &str) -> Result<Session> {
+        let path = self.path(id)?;
+        if !path.exists() {
+            bail!("no session {} in {}", id, self.dir.display());
+        }
+        Self::read(&path)
+    }
+
+    /// The most recently updated session, if any.
+    pub fn latest(&self) -> Result<Option<Session>> {
+        Ok(self.list()?.into_iter().next())
+    }
+
+    /// All sessions, most recently updated first. Unreadable files are skipped.
+    pub fn list(&self) -> Result<Vec<Session>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", self.dir.display()))
+            }
+        };
+        let mut sessions: Vec<Session> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| Self::read(&path).ok())
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+        Ok(sessions)
+    }
+
+    /// One line per saved session, newest first, for `--list-sessions`.
+    pub fn format_list(&self) -> Result<String> {
+        let sessions = self.list()?;
+        if sessions.is_empty() {
+            return Ok(format!("No saved sessions in {}", self.dir.display()));
+        }
+        let now = unix_now();
+        let lines: Vec<String> = sessions
+            .iter()
+            .map(|session| {
+                format!(
+                    "{}  {:>8}  {:<24}  {:>3} messages  {}",
+                    session.id,
+                    format_age(now.saturating_sub(session.updated_at)),
+                    session.model,
+                    session.conversation.len(),
+                    session.title()
+                )
+            })
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    // Ids come from the command line and `/load`, so anything that could
+    // leave the directory is refused
+    fn path(&self, id: &str) -> Result<PathBuf> {
+        let valid = !id.is_empty() && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f' | b'-'));
+        if !valid {
+            bail!("invalid session id {:?}", id);
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn read(path: &Path) -> Result<Session> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("invalid session file {}", path.display()))
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Message;
+
+    // A fresh directory under the system temp dir, removed on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("groq-agent-sessions-{:08x}", rand::random::<u32>()));
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn saved_sessions_load_back() {
+        let dir = TempDir::new();
+        let store = SessionStore::new(&dir.0);
+        let mut conversation = Conversation::new("You are terse.");
+        conversation.push(Message::new("user", "hi"));
+        conversation.push(Message::new("assistant", "Hello."));
+        let mut session = Session::new(&Config::default(), conversation);
+        session.failed_input = Some("what is 1 + 2?".to_string());
+
+        let path = store.save(&mut session).unwrap();
+
+        assert_eq!(path, dir.0.join(format!("{}.json", session.id)));
+        // The temporary file was renamed into place
+        let files: Vec<PathBuf> = fs::read_dir(&dir.0).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert!(!fs::read_to_string(&path).unwrap().contains("what is 1 + 2?"));
+        assert_eq!(files, [path]);
+        let loaded = store.load(&session.id).unwrap();
+        assert_eq!(loaded.id, session.id);
+        assert_eq!(loaded.conversation.len(), 3);
+        assert_eq!(loaded.title(), "hi");
+        assert!(loaded.failed_input.is_none());
+        assert_eq!(store.latest().unwrap().unwrap().id, session.id);
+    }
+
+    #[test]
+    fn ids_that_leave_the_directory_are_rejected() {
+        let dir = TempDir::new();
+        let store = SessionStore::new(&dir.0);
+        for id in ["../secrets", "/etc/passwd", "a.b", ""] {
+            let error = store.load(id).unwrap_err();
+            assert!(error.to_string().starts_with("invalid session id"), "{}", id);
+        }
+        let mut session = Session::new(&Config::default(), Conversation::new("You are terse."));
+        session.id = "../escaped".to_string();
+        assert!(store.save(&mut session).is_err());
+        assert!(!dir.0.join("../escaped.json").exists());
+    }
+}
@@ -0,0 +1,204 @@
+use reqwest::Response;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::error::{ApiError, ErrorEnvelope};
+use crate::usage::{GroqMetadata, Usage};
+use crate::{default_tool_type, ChatResponse, Choice, FunctionCall, Message, ToolCall};
+
+#[derive(Deserialize, Debug, Clone)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    // Usage only appears on the final chunk: at the top level for OpenAI,
+    // under `x_groq` for Groq
+    #[serde(default)]
+    usage: Option<Usage>,
+    #[serde(default)]
+    x_groq: Option<GroqMetadata>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct ChunkChoice {
+    #[serde(default)]
+    index: usize,
+    #[serde(default)]
+    delta: Delta,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct Delta {
+    role: Option<String>,
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCallDelta>,
+}
+
+// Streamed tool calls arrive in pieces: the first delta for an `index`
+// carries the id and function name, later ones append argument fragments.
+#[derive(Deserialize, Debug, Clone)]
+struct ToolCallDelta {
+    index: usize,
+    id: Option<String>,
+    #[serde(rename = "type")]
+    call_type: Option<String>,
+    function: Option<FunctionCallDelta>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct FunctionCallDelta {
+    name: Option<String>,
+    arguments: Option<String>,
+}
+
+/// Splits a `text/event-stream` body into the payloads of its `data:` fields.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    /// Feeds raw body bytes in and returns the data of every event they
+    /// completed. Partial events stay buffered until the next call.
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some((end, separator_len)) = find_event_end(&self.buffer) {
+            let event: Vec<u8> = self.buffer.drain(..end + separator_len).take(end).collect();
+            let event = String::from_utf8_lossy(&event);
+            let data: Vec<&str> = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect();
+            if !data.is_empty() {
+                events.push(data.join("\n"));
+            }
+        }
+        events
+    }
+}
+
+fn find_event_end(buffer: &[u8]) -> Option<(usize, usize)> {
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|i| (i, 2));
+    let crlf = buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|i| (i, 4));
+    match (lf, crlf) {
+        (Some(a), Some(b)) => Some(if a.0 <= b.0 { a } else { b }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Rebuilds complete messages, including tool calls, from streamed deltas.
+#[derive(Default)]
+struct StreamAccumulator {
+    choices: BTreeMap<usize, PartialMessage>,
+    usage: Option<Usage>,
+}
+
+#[derive(Default)]
+struct PartialMessage {
+    role: Option<String>,
+    content: String,
+    tool_calls: BTreeMap<usize, ToolCall>,
+}
+
+impl StreamAccumulator {
+    fn apply(&mut self, chunk: ChatChunk, on_token: &mut impl FnMut(&str)) {
+        if let Some(usage) = chunk
+            .usage
+            .or_else(|| chunk.x_groq.and_then(|x_groq| x_groq.usage))
+        {
+            self.usage = Some(usage);
+        }
+        for choice in chunk.choices {
+            let partial = self.choices.entry(choice.index).or_default();
+            let delta = choice.delta;
+
+            if let Some(role) = delta.role {
+                partial.role = Some(role);
+            }
+            if let Some(content) = delta.content.filter(|content| !content.is_empty()) {
+                on_token(&content);
+                partial.content.push_str(&content);
+            }
+            for call_delta in delta.tool_calls {
+                let call = partial
+                    .tool_calls
+                    .entry(call_delta.index)
+                    .or_insert_with(|| ToolCall {
+                        id: String::new(),
+                        call_type: default_tool_type(),
+                        function: FunctionCall {
+                            name: String::new(),
+                            arguments: String::new(),
+                        },
+                    });
+                if let Some(id) = call_delta.id {
+                    call.id = id;
+                }
+                if let Some(call_type) = call_delta.call_type {
+                    call.call_type = call_type;
+                }
+                if let Some(function) = call_delta.function {
+                    if let Some(name) = function.name {
+                        call.function.name.push_str(&name);
+                    }
+                    if let Some(arguments) = function.arguments {
+                        call.function.arguments.push_str(&arguments);
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> ChatResponse {
+        let choices = self
+            .choices
+            .into_values()
+            .map(|partial| {
+                let tool_calls: Vec<ToolCall> = partial.tool_calls.into_values().collect();
+                let mut message = Message::new(
+                    partial.role.as_deref().unwrap_or("assistant"),
+                    partial.content,
+                );
+                message.tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+                Choice {
+                    message: Some(message),
+                    text: None,
+                }
+            })
+            .collect();
+        ChatResponse {
+            choices,
+            usage: self.usage,
+            x_groq: None,
+        }
+    }
+}
+
+/// Reads a streamed chat completion, calling `on_token` for every content
+/// fragment as it arrives, and returns the reassembled response.
+pub async fn read_chat_stream(
+    mut response: Response,
+    mut on_token: impl FnMut(&str),
+) -> Result<ChatResponse, ApiError> {
+    let mut parser = SseParser::default();
+    let mut accumulator = StreamAccumulator::default();
+
+    while let Some(bytes) = response.chunk().await? {
+        for data in parser.push(&bytes) {
+            if data.trim() == "[DONE]" {
+                return Ok(accumulator.finish());
+            }
+            // Failures after the 200 was sent arrive as an error event
+            if let Ok(envelope) = serde_json::from_str::<ErrorEnvelope>(&data) {
+                return Err(ApiError::from_envelope(envelope));
+            }
+            let chunk: ChatChunk = serde_json::from_str(&data)?;
+            accumulator.apply(chunk, &mut on_token);
+        }
+    }
+
+    Ok(accumulator.finish())
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// Where `tracing` spans for turns, model requests and tool calls go. With
+/// neither `json_log` nor `otlp_endpoint` set, nothing is collected.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Append one JSON object per line to this file, including a record for
+    /// each span as it closes with its fields and timings.
+    pub json_log: Option<PathBuf>,
+    /// OTLP/HTTP collector endpoint, e.g. `http://localhost:4318/v1/traces`.
+    /// Needs the `otlp` cargo feature.
+    pub otlp_endpoint: Option<String>,
+    /// `tracing` filter directives, e.g. `groq_agent=debug`.
+    pub filter: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            json_log: None,
+            otlp_endpoint: None,
+            filter: "groq_agent=info".to_string(),
+        }
+    }
+}
+
+/// Keeps exporters alive; dropping it flushes spans still buffered for the
+/// OTLP collector.
+#[derive(Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otlp")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(tracer_provider) = self.tracer_provider.take() {
+            let _ = tracer_provider.shutdown();
+        }
+    }
+}
+
+/// Installs the global `tracing` subscriber described by `config`. Call it
+/// once, early, and hold on to the guard until exit.
+pub fn init(config: &TelemetryConfig) -> Result<TelemetryGuard> {
+    if config.json_log.is_none() && config.otlp_endpoint.is_none() {
+        return Ok(TelemetryGuard::default());
+    }
+
+    let json_layer = match &config.json_log {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open trace log {}", path.display()))?;
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_writer(Mutex::new(file)),
+            )
+        }
+        None => None,
+    };
+
+    #[cfg(not(feature = "otlp"))]
+    if config.otlp_endpoint.is_some() {
+        anyhow::bail!("OTLP export needs the `otlp` feature; rebuild with `--features otlp`");
+    }
+    #[cfg(feature = "otlp")]
+    let (otlp_layer, tracer_provider) = match &config.otlp_endpoint {
+        Some(endpoint) => {
+            let (layer, tracer_provider) = otlp::layer(endpoint)?;
+            (Some(layer), Some(tracer_provider))
+        }
+        None => (None, None),
+    };
+
+    let filter = EnvFilter::try_new(&config.filter)
+        .with_context(|| format!("invalid trace filter {:?}", config.filter))?;
+    let subscriber = tracing_subscriber::registry().with(filter).with(json_layer);
+    #[cfg(feature = "otlp")]
+    let subscriber = subscriber.with(otlp_layer);
+    subscriber
+        .try_init()
+        .context("a tracing subscriber is already installed")?;
+
+    Ok(TelemetryGuard {
+        #[cfg(feature = "otlp")]
+        tracer_provider,
+    })
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use anyhow::{Context, Result};
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use tracing::Subscriber;
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    pub fn layer<S>(
+        endpoint: &str,
+    ) -> Result<(OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>, SdkTracerProvider)>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()
+            .context("failed to create the OTLP exporter")?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("groq-agent").build())
+            .build();
+        let tracer = tracer_provider.tracer("groq_agent");
+        Ok((tracing_opentelemetry::layer().with_tracer(tracer), tracer_provider))
+    }
+}
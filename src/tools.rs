@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{ToolDefinition, ToolFunctionParameters};
+
+pub mod calculate;
+pub mod validate;
+
+pub use calculate::Calculate;
+pub use groq_agent_derive::ToolArgs;
+pub use validate::{validate_arguments, SchemaViolation};
+
+/// A function the model can call. Implementations are registered in a
+/// `ToolRegistry` and may do I/O, hold state, and fail.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn parameters_schema(&self) -> ToolFunctionParameters;
+
+    async fn call(&self, args: Value, ctx: &ToolContext) -> Result<ToolOutput, ToolError>;
+}
+
+/// Typed arguments for a `TypedTool`. Derive it with `#[derive(ToolArgs)]`
+/// rather than writing the schema by hand.
+pub trait ToolArgs: DeserializeOwned + Send {
+    fn parameters_schema() -> ToolFunctionParameters;
+}
+
+/// A `Tool` whose arguments are deserialized into `Self::Args` before it runs.
+/// The parameter schema comes from `Self::Args`.
+#[async_trait]
+pub trait TypedTool: Send + Sync {
+    type Args: ToolArgs;
+
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    async fn run(&self, args: Self::Args, ctx: &ToolContext) -> Result<ToolOutput, ToolError>;
+}
+
+#[async_trait]
+impl<T: TypedTool> Tool for T {
+    fn name(&self) -> &str {
+        TypedTool::name(self)
+    }
+
+    fn description(&self) -> &str {
+        TypedTool::description(self)
+    }
+
+    fn parameters_schema(&self) -> ToolFunctionParameters {
+        T::Args::parameters_schema()
+    }
+
+    async fn call(&self, args: Value, ctx: &ToolContext) -> Result<ToolOutput, ToolError> {
+        let args = serde_json::from_value(args)
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        self.run(args, ctx).await
+    }
+}
+
+/// The set of tools available to the model. Both dispatch and the `tools`
+/// array sent with each `ChatRequest` come from here, so registering a tool
+/// once is enough.
+#[derive(Default, Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a tool, replacing any previously registered tool with the same name.
+    pub fn register(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools.retain(|existing| existing.name() != tool.name());
+        self.tools.push(Arc::new(tool));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|tool| tool.name() == name).cloned()
+    }
+
+    /// Tool definitions in registration order, ready for `ChatRequest::tools`.
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|tool| ToolDefinition::from_tool(tool.as_ref()))
+            .collect()
+    }
+}
+
+/// Per-invocation details handed to a tool alongside its arguments.
+#[derive(Debug, Clone)]
+pub struct ToolContext {
+    pub call_id: String,
+}
+
+/// Successful tool result, sent back to the model verbatim.
+#[derive(Debug, Clone)]
+pub struct ToolOutput {
+    pub content: String,
+}
+
+impl ToolOutput {
+    pub fn text(content: impl Into<String>) -> Self {
+        ToolOutput {
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ToolError {
+    /// The model called a tool that isn't registered.
+    NotFound(String),
+    /// The arguments were malformed or semantically invalid.
+    InvalidArguments(String),
+    /// The arguments don't match the tool's declared parameter schema.
+    Validation(Vec<SchemaViolation>),
+    /// The arguments were fine but the tool couldn't produce a result.
+    Execution(String),
+    /// The same call was already made this turn, so it wasn't run again.
+    Repeated(String),
+}
+
+impl ToolError {
+    /// Short machine-readable category, as sent to the model.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ToolError::NotFound(_) => "not_found",
+            ToolError::InvalidArguments(_) | ToolError::Validation(_) => "invalid_arguments",
+            ToolError::Execution(_) => "execution_failed",
+            ToolError::Repeated(_) => "repeated_call",
+        }
+    }
+
+    /// Tool message content for a failed call. Errors are sent as a JSON
+    /// object so the model can tell them apart from a successful result, and
+    /// schema violations are listed individually so it can fix its arguments.
+    pub fn to_tool_content(&self) -> String {
+        let mut error = serde_json::json!({
+            "kind": self.kind(),
+            "message": self.to_string(),
+        });
+        if let ToolError::Validation(violations) = self {
+            error["violations"] = serde_json::json!(violations);
+        }
+        serde_json::json!({ "error": error }).to_string()
+    }
+}
+
+impl fmt::Display for ToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolError::NotFound(name) => write!(f, "Function '{}' not found", name),
+            ToolError::InvalidArguments(message) => write!(f, "Invalid arguments: {}", message),
+            ToolError::Validation(violations) => {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                write!(f, "Invalid arguments: {}", violations.join("; "))
+            }
+            ToolError::Execution(message) => write!(f, "{}", message),
+            ToolError::Repeated(name) => write!(
+                f,
+                "Function '{}' was already called with these arguments; use the earlier result",
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ToolError {}
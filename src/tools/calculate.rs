@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::{ToolArgs, ToolContext, ToolError, ToolOutput, TypedTool};
+
+/// Calculator tool that performs basic arithmetic operations.
+pub struct Calculate;
+
+#[derive(Debug, Deserialize, ToolArgs)]
+pub struct CalculateArgs {
+    /// First number
+    a: f64,
+    /// Second number
+    b: f64,
+    /// Operation to perform (+, -, *, /)
+    #[tool(enum = ["+", "-", "*", "/"])]
+    operation: String,
+}
+
+#[async_trait]
+impl TypedTool for Calculate {
+    type Args = CalculateArgs;
+
+    fn name(&self) -> &str {
+        "calculate"
+    }
+
+    fn description(&self) -> &str {
+        "Calculator tool that performs basic arithmetic operations"
+    }
+
+    async fn run(&self, args: CalculateArgs, _ctx: &ToolContext) -> Result<ToolOutput, ToolError> {
+        let CalculateArgs { a, b, operation } = args;
+
+        let result = match operation.as_str() {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" if b != 0.0 => a / b,
+            "/" => return Err(ToolError::Execution("Division by zero".to_string())),
+            op => {
+                return Err(ToolError::InvalidArguments(format!(
+                    "Unknown operation '{}'",
+                    op
+                )))
+            }
+        };
+
+        Ok(ToolOutput::text(format!(
+            "The result of {} {} {} is {}",
+            a, operation, b, result
+        )))
+    }
+}
@@ -0,0 +1,291 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::ToolFunctionParameters;
+
+/// One way in which a tool's arguments don't match its declared schema.
+#[derive(Serialize, Debug, Clone)]
+pub struct SchemaViolation {
+    /// Dotted path to the offending value, e.g. `filters.limit` or `ids[2]`.
+    /// Empty for the arguments object itself.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Checks `args` against the subset of JSON Schema that tool definitions use:
+/// `type`, `properties`, `required`, `additionalProperties: false`, `enum`,
+/// numeric `minimum`/`maximum` (and their exclusive forms), string lengths,
+/// and array `items`/lengths. `null` is accepted for properties that aren't
+/// required, as serde reads it as `None`. Returns every violation rather
+/// than the first.
+pub fn validate_arguments(
+    schema: &ToolFunctionParameters,
+    args: &Value,
+) -> Result<(), Vec<SchemaViolation>> {
+    let schema = serde_json::to_value(schema).expect("tool parameter schema serializes");
+    let mut violations = Vec::new();
+    validate_value(&schema, args, "", &mut violations);
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+fn validate_value(schema: &Value, value: &Value, path: &str, violations: &mut Vec<SchemaViolation>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    let mut violation = |message: String| {
+        violations.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| matches_type(t, value)) {
+            violation(format!(
+                "expected {}, got {}",
+                allowed.join(" or "),
+                type_name(value)
+            ));
+            // Further keywords would only repeat the type mismatch
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            violation(format!(
+                "must be one of {}",
+                Value::Array(options.clone())
+            ));
+        }
+    }
+
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+            if let Some(min) = bound("minimum").filter(|min| n < *min) {
+                violation(format!("must be >= {}", min));
+            }
+            if let Some(max) = bound("maximum").filter(|max| n > *max) {
+                violation(format!("must be <= {}", max));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|min| n <= *min) {
+                violation(format!("must be > {}", min));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|max| n >= *max) {
+                violation(format!("must be < {}", max));
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64).filter(|min| len < *min) {
+                violation(format!("must be at least {} characters", min));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64).filter(|max| len > *max) {
+                violation(format!("must be at most {} characters", max));
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|min| len < *min) {
+                violation(format!("must have at least {} items", min));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|max| len > *max) {
+                violation(format!("must have at most {} items", max));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    validate_value(item_schema, item, &format!("{}[{}]", path, index), violations);
+                }
+            }
+        }
+        Value::Object(fields) => validate_object(schema, fields, path, violations),
+        _ => {}
+    }
+}
+
+fn validate_object(
+    schema: &Map<String, Value>,
+    fields: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let child_path = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+
+    let required: Vec<&str> = match schema.get("required") {
+        Some(Value::Array(required)) => required.iter().filter_map(Value::as_str).collect(),
+        _ => vec![],
+    };
+    for &name in &required {
+        if !fields.contains_key(name) {
+            violations.push(SchemaViolation {
+                path: child_path(name),
+                message: "is required".to_string(),
+            });
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
+    for (name, value) in fields {
+        match properties.and_then(|properties| properties.get(name)) {
+            // Models often send `null` for optional parameters they don't
+            // use, which deserializes to `None` just like a missing field
+            Some(_) if value.is_null() && !required.contains(&name.as_str()) => {}
+            Some(property_schema) => {
+                validate_value(property_schema, value, &child_path(name), violations)
+            }
+            None if closed => violations.push(SchemaViolation {
+                path: child_path(name),
+                message: "is not an allowed property".to_string(),
+            }),
+            None => {}
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        // Unknown types are not ours to reject
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToolArgs;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, ToolArgs)]
+    #[allow(dead_code)]
+    struct SearchArgs {
+        /// Text to look for
+        query: String,
+        /// Maximum number of results
+        limit: Option<u32>,
+    }
+
+    #[derive(Deserialize, ToolArgs)]
+    struct Filter {
+        #[tool(enum = ["open", "closed"])]
+        status: String,
+    }
+
+    #[derive(Deserialize, ToolArgs)]
+    #[serde(rename_all = "camelCase")]
+    #[allow(dead_code)]
+    struct ReportArgs {
+        #[serde(rename = "q")]
+        query: String,
+        #[tool(minimum = 1, maximum = 100)]
+        page_size: u32,
+        tags: Vec<String>,
+        #[serde(default)]
+        include_archived: bool,
+        filter: Option<Filter>,
+        #[serde(skip)]
+        cursor: Option<String>,
+    }
+
+    #[test]
+    fn derived_schemas_follow_serde_names() {
+        let schema = serde_json::to_value(ReportArgs::parameters_schema()).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "q": { "type": "string" },
+                    "pageSize": { "type": "integer", "minimum": 1, "maximum": 100 },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "includeArchived": { "type": "boolean" },
+                    "filter": {
+                        "type": "object",
+                        "properties": { "status": { "type": "string", "enum": ["open", "closed"] } },
+                        "required": ["status"],
+                    },
+                },
+                "required": ["q", "pageSize", "tags"],
+            })
+        );
+    }
+
+    #[test]
+    fn derived_schemas_validate_what_serde_accepts() {
+        let schema = ReportArgs::parameters_schema();
+        let args = json!({ "q": "rust", "pageSize": 10, "tags": ["a"], "filter": { "status": "open" } });
+        assert!(validate_arguments(&schema, &args).is_ok());
+        let parsed: ReportArgs = serde_json::from_value(args).unwrap();
+        assert_eq!((parsed.page_size, parsed.include_archived), (10, false));
+        assert_eq!(parsed.filter.unwrap().status, "open");
+
+        let args = json!({ "q": "rust", "pageSize": 0, "tags": [1], "filter": { "status": "pending" } });
+        let violations = validate_arguments(&schema, &args).unwrap_err();
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["filter.status", "pageSize", "tags[0]"]);
+    }
+
+    #[test]
+    fn optional_fields_accept_null() {
+        let args = json!({ "query": "rust", "limit": null });
+        assert!(validate_arguments(&SearchArgs::parameters_schema(), &args).is_ok());
+        let parsed: SearchArgs = serde_json::from_value(args).unwrap();
+        assert_eq!(parsed.limit, None);
+    }
+
+    #[test]
+    fn required_fields_reject_null() {
+        let args = json!({ "query": null, "limit": "ten" });
+        let violations = validate_arguments(&SearchArgs::parameters_schema(), &args).unwrap_err();
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, ["limit", "query"]);
+    }
+}
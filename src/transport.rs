@@ -0,0 +1,67 @@
+use async_trait::async_trait;
+use reqwest::{Client, Method, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::error::ApiError;
+
+/// An outgoing JSON request, in a form that can be recorded and compared.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    /// Header names are lowercase.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Value,
+}
+
+impl HttpRequest {
+    pub fn post(url: impl Into<String>, body: &impl Serialize) -> Result<Self, ApiError> {
+        Ok(HttpRequest {
+            method: "POST".to_string(),
+            url: url.into(),
+            headers: BTreeMap::from([("content-type".to_string(), "application/json".to_string())]),
+            body: serde_json::to_value(body)?,
+        })
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.insert(name.to_ascii_lowercase(), value.into());
+        self
+    }
+}
+
+/// Sends provider requests over HTTP. Swapping the transport lets tests
+/// record real exchanges and replay them offline.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Returns the response whatever its status; providers classify errors.
+    async fn send(&self, request: &HttpRequest) -> Result<Response, ApiError>;
+}
+
+/// The network transport, backed by a shared `reqwest::Client`.
+#[derive(Default, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: &HttpRequest) -> Result<Response, ApiError> {
+        let method = Method::from_bytes(request.method.as_bytes())
+            .map_err(|e| ApiError::Decode(format!("invalid HTTP method {}: {}", request.method, e)))?;
+        let mut builder = self.client.request(method, &request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.json(&request.body).send().await?)
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Token counts for one or more requests. Groq also reports how long the
+/// request spent queued and generating, in seconds.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_time: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_time: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_time: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_time: Option<f64>,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        fn add_time(total: &mut Option<f64>, other: Option<f64>) {
+            if let Some(other) = other {
+                *total = Some(total.unwrap_or_default() + other);
+            }
+        }
+
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        add_time(&mut self.queue_time, other.queue_time);
+        add_time(&mut self.prompt_time, other.prompt_time);
+        add_time(&mut self.completion_time, other.completion_time);
+        add_time(&mut self.total_time, other.total_time);
+    }
+}
+
+/// Groq-specific response metadata (`x_groq`). Streamed responses carry
+/// their usage here, in the final chunk.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct GroqMetadata {
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+/// Price of a model in USD per million tokens.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input_per_million
+            + usage.completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Built-in prices for common Groq models; `[pricing]` in the config file
+/// overrides these and adds others.
+pub fn default_price(model: &str) -> Option<ModelPrice> {
+    let (input_per_million, output_per_million) = match model {
+        "llama-3.3-70b-versatile" => (0.59, 0.79),
+        "llama-3.1-8b-instant" => (0.05, 0.08),
+        "gemma2-9b-it" => (0.20, 0.20),
+        _ => return None,
+    };
+    Some(ModelPrice {
+        input_per_million,
+        output_per_million,
+    })
+}
+
+/// Running totals over a turn or a whole session.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub usage: Usage,
+    /// Estimated cost in USD of the requests whose model has a known price.
+    pub cost: Option<f64>,
+}
+
+impl UsageTotals {
+    pub fn record(&mut self, usage: &Usage, price: Option<ModelPrice>) {
+        self.requests += 1;
+        self.usage.add(usage);
+        if let Some(price) = price {
+            self.cost = Some(self.cost.unwrap_or_default() + price.cost(usage));
+        }
+    }
+
+    pub fn merge(&mut self, other: &UsageTotals) {
+        self.requests += other.requests;
+        self.usage.add(&other.usage);
+        if let Some(cost) = other.cost {
+            self.cost = Some(self.cost.unwrap_or_default() + cost);
+        }
+    }
+}
+
+impl fmt::Display for UsageTotals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} prompt + {} completion = {} tokens ({} request{}",
+            self.usage.prompt_tokens,
+            self.usage.completion_tokens,
+            self.usage.total_tokens,
+            self.requests,
+            if self.requests == 1 { "" } else { "s" }
+        )?;
+        if let Some(total_time) = self.usage.total_time {
+            write!(f, ", {:.2}s", total_time)?;
+        }
+        write!(f, ")")?;
+        if let Some(cost) = self.cost {
+            write!(f, ", est. ${:.6}", cost)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,542 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use groq_agent::agent::DEFAULT_SYSTEM_PROMPT;
+use groq_agent::config::ProviderKind;
+use groq_agent::context::{ContextConfig, ContextStrategy, TrimPolicy};
+use groq_agent::mock::{MockResponse, MockServer};
+use groq_agent::tools::Calculate;
+use groq_agent::{
+    Agent, AgentBuilder, AgentEvent, AgentLimits, ApiError, Config, Conversation, Message, Tool, ToolContext, ToolError,
+    ToolFunctionParameters,
+    ToolOutput, ToolRegistry,
+};
+
+struct Harness {
+    server: MockServer,
+    agent: Agent,
+    events: Arc<Mutex<Vec<AgentEvent>>>,
+}
+
+impl Harness {
+    async fn start(responses: Vec<MockResponse>, stream: bool) -> Self {
+        let config = Config {
+            stream,
+            ..Config::default()
+        };
+        Self::with(responses, config, ToolRegistry::new().register(Calculate)).await
+    }
+
+    async fn with(responses: Vec<MockResponse>, config: Config, tools: ToolRegistry) -> Self {
+        Self::build(responses, config, Agent::builder().tools(tools)).await
+    }
+
+    // `config` is pointed at the mock server
+    async fn build(responses: Vec<MockResponse>, config: Config, builder: AgentBuilder) -> Self {
+        let server = MockServer::start().await.unwrap();
+        for response in responses {
+            server.enqueue(response);
+        }
+        let config = Config {
+            provider: ProviderKind::OpenAi,
+            base_url: Some(server.base_url()),
+            ..config
+        };
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let agent = builder
+            .config(config)
+            .subscribe(move |event: &AgentEvent| sink.lock().unwrap().push(event.clone()))
+            .build()
+            .unwrap();
+        Harness { server, agent, events }
+    }
+
+    // The `messages` of every request sent, as they went over the wire
+    fn sent(&self) -> Vec<Value> {
+        self.server
+            .requests()
+            .into_iter()
+            .map(|request| request.body["messages"].clone())
+            .collect()
+    }
+
+    fn events(&self) -> Vec<AgentEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+fn system() -> Value {
+    json!({ "role": "system", "content": DEFAULT_SYSTEM_PROMPT })
+}
+
+fn user(content: &str) -> Value {
+    json!({ "role": "user", "content": content })
+}
+
+#[tokio::test]
+async fn plain_text_answers_in_one_request() {
+    let mut harness = Harness::start(vec![MockResponse::text("Hi there!").with_usage(12, 3)], false).await;
+
+    let answer = harness.agent.run("Hello").await.unwrap();
+
+    assert_eq!(answer, "Hi there!");
+    assert_eq!(harness.sent(), [json!([system(), user("Hello")])]);
+    let request = &harness.server.requests()[0];
+    assert_eq!(request.path, "/openai/v1/chat/completions");
+    assert_eq!(request.body["model"], "llama-3.3-70b-versatile");
+    assert_eq!(request.body["tools"][0]["function"]["name"], "calculate");
+    assert_eq!(harness.agent.usage().usage.total_tokens, 15);
+}
+
+#[tokio::test]
+async fn native_tool_calls_are_answered_with_tool_messages() {
+    let mut harness = Harness::start(
+        vec![
+            MockResponse::tool_call("call_1", "calculate", json!({"a": 6, "b": 7, "operation": "*"})),
+            MockResponse::text("6 * 7 is 42."),
+        ],
+        false,
+    )
+    .await;
+
+    let answer = harness.agent.run("What is 6 * 7?").await.unwrap();
+
+    assert_eq!(answer, "6 * 7 is 42.");
+    let call = json!({
+        "id": "call_1",
+        "type": "function",
+        "function": { "name": "calculate", "arguments": r#"{"a":6,"b":7,"operation":"*"}"# },
+    });
+    assert_eq!(
+        harness.sent(),
+        [
+            json!([system(), user("What is 6 * 7?")]),
+            json!([
+                system(),
+                user("What is 6 * 7?"),
+                { "role": "assistant", "content": "", "tool_calls": [call] },
+                { "role": "tool", "content": "The result of 6 * 7 is 42", "tool_call_id": "call_1", "name": "calculate" },
+            ]),
+        ]
+    );
+    // Once the results are in, the model is free to answer
+    assert_eq!(harness.server.requests()[1].body["tool_choice"], "auto");
+}
+
+#[tokio::test]
+async fn inline_function_tags_get_synthesized_call_ids() {
+    let mut harness = Harness::start(
+        vec![
+            MockResponse::inline_call("calculate", json!({"a": 1, "b": 0, "operation": "/"})),
+            MockResponse::text("That divides by zero."),
+        ],
+        false,
+    )
+    .await;
+
+    harness.agent.run("What is 1 / 0?").await.unwrap();
+
+    let sent = harness.sent();
+    assert_eq!(sent.len(), 2);
+    let follow_up = sent[1].as_array().unwrap();
+    assert_eq!(follow_up.len(), 4);
+    assert_eq!(
+        follow_up[2]["content"],
+        r#"<function=calculate{"a":1,"b":0,"operation":"/"}></function>"#
+    );
+    assert_eq!(follow_up[2]["tool_calls"][0]["id"], "inline_call_0");
+    assert_eq!(follow_up[3]["role"], "tool");
+    assert_eq!(follow_up[3]["tool_call_id"], "inline_call_0");
+    let error: Value = serde_json::from_str(follow_up[3]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(error["error"]["kind"], "execution_failed");
+    assert_eq!(error["error"]["message"], "Division by zero");
+}
+
+#[tokio::test]
+async fn streamed_chunks_are_emitted_and_joined() {
+    let mut harness = Harness::start(
+        vec![MockResponse::streamed_text(["Hello", ", ", "world", "!"]).with_usage(9, 4)],
+        true,
+    )
+    .await;
+
+    let answer = harness.agent.run("Greet me").await.unwrap();
+
+    assert_eq!(answer, "Hello, world!");
+    let request = &harness.server.requests()[0];
+    assert_eq!(request.body["stream"], true);
+    assert_eq!(request.body["stream_options"], json!({ "include_usage": true }));
+    let tokens: Vec<String> = harness
+        .events()
+        .into_iter()
+        .filter_map(|event| match event {
+            AgentEvent::TokenDelta(token) => Some(token),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(tokens, ["Hello", ", ", "world", "!"]);
+    assert_eq!(harness.agent.usage().usage.total_tokens, 13);
+}
+
+#[tokio::test]
+async fn streamed_tool_calls_are_reassembled() {
+    let chunk = |delta: Value| json!({ "choices": [{ "index": 0, "delta": delta }] });
+    let mut harness = Harness::start(
+        vec![
+            MockResponse::stream([
+                chunk(json!({ "role": "assistant", "tool_calls": [{
+                    "index": 0, "id": "call_9", "type": "function",
+                    "function": { "name": "calculate", "arguments": "" },
+                }] })),
+                chunk(json!({ "tool_calls": [{ "index": 0, "function": { "arguments": r#"{"a":2,"# } }] })),
+                chunk(json!({ "tool_calls": [{ "index": 0, "function": { "arguments": r#""b":2,"operation":"+"}"# } }] })),
+            ]),
+            MockResponse::streamed_text(["4"]),
+        ],
+        true,
+    )
+    .await;
+
+    assert_eq!(harness.agent.run("2 + 2?").await.unwrap(), "4");
+
+    let sent = harness.sent();
+    assert_eq!(sent[1][2]["tool_calls"][0]["function"]["arguments"], r#"{"a":2,"b":2,"operation":"+"}"#);
+    assert_eq!(sent[1][3]["content"], "The result of 2 + 2 is 4");
+}
+
+#[tokio::test]
+async fn rate_limits_resend_the_same_request() {
+    let mut harness = Harness::start(
+        vec![MockResponse::rate_limited(0), MockResponse::text("Finally.")],
+        false,
+    )
+    .await;
+
+    assert_eq!(harness.agent.run("Hello").await.unwrap(), "Finally.");
+
+    let requests = harness.server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].body, requests[1].body);
+    assert!(harness
+        .events()
+        .iter()
+        .any(|event| matches!(event, AgentEvent::Retrying { attempt: 2, .. })));
+    assert_eq!(harness.agent.usage().requests, 1);
+}
+
+#[tokio::test]
+async fn malformed_json_fails_the_turn_without_retrying() {
+    let mut harness = Harness::start(
+        vec![MockResponse::raw(r#"{"choices": [{"message": "#), MockResponse::text("unused")],
+        false,
+    )
+    .await;
+
+    let error = harness.agent.run("Hello").await.unwrap_err();
+
+    assert!(matches!(error.downcast_ref::<ApiError>(), Some(ApiError::Decode(_))));
+    assert_eq!(harness.server.requests().len(), 1);
+    assert_eq!(harness.server.pending(), 1);
+    assert_eq!(harness.agent.conversation().len(), 1);
+}
+
+#[tokio::test]
+async fn turns_accumulate_in_later_requests() {
+    let mut harness = Harness::start(
+        vec![MockResponse::text("First."), MockResponse::text("Second.")],
+        false,
+    )
+    .await;
+
+    harness.agent.run("One").await.unwrap();
+    harness.agent.run("Two").await.unwrap();
+
+    assert_eq!(
+        harness.sent()[1],
+        json!([
+            system(),
+            user("One"),
+            { "role": "assistant", "content": "First." },
+            user("Two"),
+        ])
+    );
+    assert_eq!(harness.server.pending(), 0);
+}
+
+// Never returns, like a tool stuck on a dead connection
+struct Hang;
+
+#[async_trait]
+impl Tool for Hang {
+    fn name(&self) -> &str {
+        "hang"
+    }
+
+    fn description(&self) -> &str {
+        "Never finishes"
+    }
+
+    fn parameters_schema(&self) -> ToolFunctionParameters {
+        ToolFunctionParameters::object(json!({}), vec![])
+    }
+
+    async fn call(&self, _args: Value, _ctx: &ToolContext) -> Result<ToolOutput, ToolError> {
+        std::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn hung_tools_time_out_and_the_model_must_answer() {
+    let config = Config {
+        limits: AgentLimits {
+            turn_timeout_secs: 1,
+            ..AgentLimits::default()
+        },
+        ..Config::default()
+    };
+    let tools = ToolRegistry::new().register(Calculate).register(Hang);
+    let mut harness = Harness::with(
+        vec![
+            MockResponse::tool_calls(serde_json::from_value(json!([
+                { "id": "call_1", "type": "function", "function": { "name": "hang", "arguments": "{}" } },
+                { "id": "call_2", "type": "function", "function": { "name": "calculate", "arguments": r#"{"a":1,"b":1,"operation":"+"}"# } },
+            ])).unwrap()),
+            MockResponse::text("The first tool didn't answer in time; 1 + 1 is 2."),
+        ],
+        config,
+        tools,
+    )
+    .await;
+
+    let started = Instant::now();
+    let answer = harness.agent.run("Hang, then add").await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(answer, "The first tool didn't answer in time; 1 + 1 is 2.");
+    let follow_up = harness.server.requests()[1].messages();
+    let contents: Vec<(&str, &str)> = follow_up[3..]
+        .iter()
+        .map(|message| (message.role.as_str(), message.content.as_str()))
+        .collect();
+    assert_eq!(
+        contents,
+        [
+            ("tool", r#"{"error":{"kind":"execution_failed","message":"timed out"}}"#),
+            ("tool", "The result of 1 + 1 is 2"),
+            (
+                "system",
+                "Tool limit reached: the 1s time budget for this turn ran out. Do not call any more tools. Answer the user now using the results you already have.",
+            ),
+        ]
+    );
+    assert_eq!(harness.server.requests()[1].body["tool_choice"], "none");
+}
+
+// About 100 tokens by the four-characters-per-token estimate
+fn long(label: &str) -> String {
+    format!("{:<400}", label)
+}
+
+#[tokio::test]
+async fn oldest_turns_are_summarized_into_one_note() {
+    let mut config = Config {
+        max_tokens: Some(100),
+        context: ContextConfig {
+            strategy: ContextStrategy::Summarize,
+            ..ContextConfig::default()
+        },
+        ..Config::default()
+    };
+    // 800 tokens left for messages after the reply; summarizing starts at 640
+    config.context.limits.insert(config.model.clone(), 900);
+    let harness = Harness::with(
+        vec![
+            MockResponse::text(long("a1")),
+            MockResponse::text(long("a2")),
+            MockResponse::text(long("a3")),
+            MockResponse::text("S1"),
+            MockResponse::text(long("a4")),
+            MockResponse::text(long("a5")),
+            MockResponse::text("S2"),
+            MockResponse::text(long("a6")),
+        ],
+        config,
+        ToolRegistry::new(),
+    )
+    .await;
+
+    let mut conversation = harness.agent.new_conversation();
+    for (index, input) in ["u1", "u2", "u3", "u4", "u5", "u6"].into_iter().enumerate() {
+        // The third turn is pinned and must survive both compactions
+        if index == 2 {
+            conversation.push_pinned(Message::new("user", long(input)));
+        } else {
+            conversation.push(Message::new("user", long(input)));
+        }
+        harness.agent.chat(&mut conversation).await.unwrap();
+
+        if index == 3 {
+            let contents: Vec<&str> = conversation
+                .messages()
+                .iter()
+                .map(|message| message.content.trim_end())
+                .collect();
+            assert_eq!(
+                contents,
+                [DEFAULT_SYSTEM_PROMPT, "Summary of the earlier conversation:\nS1", "u3", "a3", "u4", "a4"]
+            );
+            assert_eq!(conversation.messages()[1].role, "system");
+            // The pinned message moved from index 5 to 2 with its pin
+            assert!(conversation.is_pinned(0) && conversation.is_pinned(2));
+            assert!(!conversation.is_pinned(5));
+        }
+    }
+
+    let requests = harness.server.requests();
+    assert_eq!(requests.len(), 8);
+    // The first summary request covers the two oldest turns and nothing else
+    let summary_request = requests[3].messages();
+    assert_eq!(summary_request.len(), 2);
+    assert_eq!(summary_request[0].role, "system");
+    let transcript = &summary_request[1].content;
+    for label in ["u1", "a1", "u2", "a2"] {
+        assert!(transcript.contains(label), "{} missing", label);
+    }
+    assert!(!transcript.contains("u3") && !transcript.contains("u4"));
+    assert!(requests[3].body.get("tools").is_none());
+    assert_eq!(requests[4].messages()[1].content, "Summary of the earlier conversation:\nS1");
+
+    // The second summary folds the first one in rather than adding a note
+    let transcript = &requests[6].messages()[1].content;
+    assert!(transcript.contains("S1") && transcript.contains("u4") && !transcript.contains("u3"));
+    let contents: Vec<&str> = conversation
+        .messages()
+        .iter()
+        .map(|message| message.content.trim_end())
+        .collect();
+    assert_eq!(
+        contents,
+        [DEFAULT_SYSTEM_PROMPT, "Summary of the earlier conversation:\nS2", "u3", "a3", "u5", "a5", "u6", "a6"]
+    );
+    assert!(conversation.is_pinned(2));
+
+    let compactions = conversation.compactions();
+    assert_eq!(compactions.len(), 2);
+    assert_eq!(compactions[0].messages_summarized, 4);
+    assert_eq!(compactions[1].messages_summarized, 3);
+    assert!(compactions.iter().all(|compaction| compaction.tokens_after < compaction.tokens_before));
+    let compacted = harness
+        .events()
+        .iter()
+        .filter(|event| matches!(event, AgentEvent::ContextCompacted(_)))
+        .count();
+    assert_eq!(compacted, 2);
+    // Summary requests are reported like the turns' own requests
+    let events = harness.events();
+    let sent = events.iter().filter(|event| matches!(event, AgentEvent::RequestSent { .. })).count();
+    let usage = events.iter().filter(|event| matches!(event, AgentEvent::Usage { .. })).count();
+    assert_eq!((sent, usage), (8, 8));
+}
+
+#[tokio::test]
+async fn agents_without_tools_send_no_tool_choice() {
+    let mut harness = Harness::with(vec![MockResponse::text("Hi!")], Config::default(), ToolRegistry::new()).await;
+
+    assert_eq!(harness.agent.run("Hello").await.unwrap(), "Hi!");
+
+    let body = &harness.server.requests()[0].body;
+    assert!(body.get("tools").is_none());
+    assert!(body.get("tool_choice").is_none());
+}
+
+// Sends the system prompt and the latest message only
+struct LatestOnly;
+
+impl TrimPolicy for LatestOnly {
+    fn trim(&self, conversation: &Conversation, _budget: u64) -> Vec<Message> {
+        let messages = conversation.messages();
+        vec![messages[0].clone(), messages[messages.len() - 1].clone()]
+    }
+}
+
+#[tokio::test]
+async fn builder_trim_policy_replaces_the_configured_one() {
+    let mut harness = Harness::build(
+        vec![MockResponse::text("a1"), MockResponse::text("a2")],
+        Config::default(),
+        Agent::builder().trim_policy(Box::new(LatestOnly)),
+    )
+    .await;
+
+    harness.agent.run("u1").await.unwrap();
+    harness.agent.run("u2").await.unwrap();
+
+    assert_eq!(harness.sent(), [json!([system(), user("u1")]), json!([system(), user("u2")])]);
+    // The full history is kept even though less was sent
+    assert_eq!(harness.agent.conversation().len(), 5);
+    let trimmed = harness
+        .events()
+        .iter()
+        .filter(|event| matches!(event, AgentEvent::ContextTrimmed { dropped_messages: 2 }))
+        .count();
+    assert_eq!(trimmed, 1);
+}
+
+#[tokio::test]
+async fn repeated_calls_are_answered_without_running() {
+    let args = json!({"a": 6, "b": 7, "operation": "*"});
+    let mut harness = Harness::start(
+        vec![
+            MockResponse::tool_call("call_1", "calculate", args.clone()),
+            MockResponse::tool_call("call_2", "calculate", args),
+            MockResponse::text("It is 42."),
+        ],
+        false,
+    )
+    .await;
+
+    assert_eq!(harness.agent.run("What is 6 * 7?").await.unwrap(), "It is 42.");
+
+    let sent = harness.sent();
+    assert_eq!(sent.len(), 3);
+    let repeat = &sent[2].as_array().unwrap()[5];
+    assert_eq!(repeat["tool_call_id"], "call_2");
+    let error: Value = serde_json::from_str(repeat["content"].as_str().unwrap()).unwrap();
+    assert_eq!(error["error"]["kind"], "repeated_call");
+    let started: Vec<String> = harness
+        .events()
+        .iter()
+        .filter_map(|event| match event {
+            AgentEvent::ToolStarted { call_id, .. } => Some(call_id.clone()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(started, ["call_1"]);
+    assert_eq!(harness.server.requests()[2].body["tool_choice"], "none");
+}
+
+#[tokio::test]
+async fn zero_tool_rounds_offers_no_tools() {
+    let config = Config {
+        limits: AgentLimits {
+            max_tool_rounds: 0,
+            ..AgentLimits::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = Harness::with(
+        vec![MockResponse::text("No tools needed.")],
+        config,
+        ToolRegistry::new().register(Calculate),
+    )
+    .await;
+
+    assert_eq!(harness.agent.run("What is 6 * 7?").await.unwrap(), "No tools needed.");
+
+    let body = &harness.server.requests()[0].body;
+    assert!(body.get("tools").is_none());
+    assert!(body.get("tool_choice").is_none());
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use groq_agent::cassette::{Cassette, RecordingTransport, ReplayTransport};
+use groq_agent::providers::OpenAiCompatible;
+use groq_agent::tools::Calculate;
+use groq_agent::transport::Transport;
+use groq_agent::{Agent, AgentEvent, ApiError, Config, ToolRegistry};
+
+const QUESTION: &str = "What is 2 * 3 and 1 / 0?";
+const ANSWER: &str = "2 * 3 is 6, and 1 / 0 can't be computed because it divides by zero.";
+
+fn cassette(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cassettes")
+        .join(format!("{}.json", name))
+}
+
+struct Harness {
+    agent: Agent,
+    events: Arc<Mutex<Vec<AgentEvent>>>,
+}
+
+impl Harness {
+    // The key differs from the one used when recording; auth headers are
+    // neither stored nor matched
+    fn new(transport: Arc<dyn Transport>, config: Config) -> Self {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let agent = Agent::builder()
+            .provider(OpenAiCompatible::groq("test-key".to_string()).with_transport(transport))
+            .tools(ToolRegistry::new().register(Calculate))
+            .config(config)
+            .subscribe(move |event: &AgentEvent| sink.lock().unwrap().push(event.clone()))
+            .build()
+            .unwrap();
+        Harness { agent, events }
+    }
+
+    fn replay(name: &str, config: Config) -> (Self, Arc<ReplayTransport>) {
+        let replay = Arc::new(ReplayTransport::load(&cassette(name)).unwrap());
+        (Self::new(replay.clone(), config), replay)
+    }
+
+    fn events(&self) -> Vec<AgentEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+fn roles(agent: &Agent) -> Vec<String> {
+    agent
+        .conversation()
+        .messages()
+        .iter()
+        .map(|message| message.role.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn tool_loop_runs_both_calls_and_answers() {
+    let (mut harness, replay) = Harness::replay("tool_loop", Config::default());
+
+    let answer = harness.agent.run(QUESTION).await.unwrap();
+
+    assert_eq!(answer, ANSWER);
+    assert_eq!(roles(&harness.agent), ["system", "user", "assistant", "tool", "tool", "assistant"]);
+    let messages = harness.agent.conversation().messages();
+    let tool_calls = messages[2].tool_calls.as_ref().unwrap();
+    assert_eq!(tool_calls.len(), 2);
+    assert_eq!(messages[3].tool_call_id.as_deref(), Some("call_1"));
+    assert_eq!(messages[3].content, "The result of 2 * 3 is 6");
+    assert_eq!(messages[4].tool_call_id.as_deref(), Some("call_2"));
+    assert!(messages[4].content.contains("\"execution_failed\""));
+    assert_eq!(harness.agent.usage().requests, 2);
+    assert_eq!(harness.agent.usage().usage.prompt_tokens, 240);
+    assert_eq!(replay.remaining(), 0);
+
+    let events = harness.events();
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AgentEvent::ToolFinished { call_id, .. } if call_id == "call_1"
+    )));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AgentEvent::ToolFailed { call_id, error, .. } if call_id == "call_2" && error == "Division by zero"
+    )));
+    assert!(matches!(events.last(), Some(AgentEvent::FinalAnswer { content }) if content == ANSWER));
+}
+
+#[tokio::test]
+async fn streamed_replies_are_reassembled() {
+    let config = Config {
+        stream: true,
+        ..Config::default()
+    };
+    let (mut harness, replay) = Harness::replay("streaming", config);
+
+    let answer = harness.agent.run(QUESTION).await.unwrap();
+
+    // Each word arrives as its own delta
+    let streamed: String = harness
+        .events()
+        .iter()
+        .filter_map(|event| match event {
+            AgentEvent::TokenDelta(token) => Some(token.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(streamed.trim_end(), ANSWER);
+    assert_eq!(answer.trim_end(), ANSWER);
+    let tool_calls = harness.agent.conversation().messages()[2].tool_calls.clone().unwrap();
+    assert_eq!(tool_calls[0].function.arguments, r#"{"a":2,"b":3,"operation":"*"}"#);
+    assert_eq!(tool_calls[1].function.arguments, r#"{"a":1,"b":0,"operation":"/"}"#);
+    // Streamed usage comes from the final chunk's `x_groq` field
+    assert_eq!(harness.agent.usage().usage.total_tokens, 280);
+    assert_eq!(replay.remaining(), 0);
+}
+
+#[tokio::test]
+async fn auth_errors_are_classified_and_the_turn_is_dropped() {
+    let (mut harness, _) = Harness::replay("auth_error", Config::default());
+
+    let error = harness.agent.run("auth check").await.unwrap_err();
+
+    assert!(matches!(
+        error.downcast_ref::<ApiError>(),
+        Some(ApiError::Auth { message }) if message.contains("Invalid API Key")
+    ));
+    assert_eq!(roles(&harness.agent), ["system"]);
+}
+
+#[tokio::test]
+async fn context_length_errors_are_classified() {
+    let (mut harness, _) = Harness::replay("context_length", Config::default());
+
+    let error = harness.agent.run("long story").await.unwrap_err();
+
+    assert!(matches!(error.downcast_ref::<ApiError>(), Some(ApiError::ContextLength { .. })));
+}
+
+#[tokio::test]
+async fn rate_limits_are_retried_after_the_server_delay() {
+    let (mut harness, replay) = Harness::replay("rate_limited", Config::default());
+
+    let answer = harness.agent.run("busy hello").await.unwrap();
+
+    assert_eq!(answer, "Hello! How can I help?");
+    assert_eq!(replay.remaining(), 0);
+    assert!(harness.events().iter().any(|event| matches!(
+        event,
+        AgentEvent::Retrying { attempt: 2, delay, .. } if delay.is_zero()
+    )));
+}
+
+#[tokio::test]
+async fn unrecorded_requests_fail_without_touching_the_network() {
+    let (mut harness, replay) = Harness::replay("tool_loop", Config::default());
+
+    let error = harness.agent.run("Something else entirely").await.unwrap_err();
+
+    assert!(matches!(
+        error.downcast_ref::<ApiError>(),
+        Some(ApiError::InvalidRequest { status, .. }) if status.as_u16() == 501
+    ));
+    assert_eq!(replay.remaining(), 2);
+}
+
+#[tokio::test]
+async fn recordings_replay_and_leave_out_credentials() {
+    let path = std::env::temp_dir().join(format!("groq-agent-recording-{}.json", std::process::id()));
+    let source = Arc::new(ReplayTransport::load(&cassette("tool_loop")).unwrap());
+    let recorder = Arc::new(RecordingTransport::new(source, &path));
+    let mut harness = Harness::new(recorder, Config::default());
+    harness.agent.run(QUESTION).await.unwrap();
+
+    let recorded = Cassette::load(&path).unwrap();
+    assert_eq!(recorded.interactions.len(), 2);
+    assert!(recorded
+        .interactions
+        .iter()
+        .all(|interaction| !interaction.request.headers.contains_key("authorization")));
+
+    let replayed = Arc::new(ReplayTransport::new(recorded));
+    let mut rerun = Harness::new(replayed.clone(), Config::default());
+    assert_eq!(rerun.agent.run(QUESTION).await.unwrap(), ANSWER);
+    assert_eq!(replayed.remaining(), 0);
+    std::fs::remove_file(path).unwrap();
+}